@@ -1,15 +1,28 @@
+DROP TABLE Invoice;
+DROP TABLE LedgerEntry;
+DROP TABLE BookingNight;
+DROP TABLE BookingChange;
+DROP TABLE AuthEvent;
+DROP TABLE RecoveryCode;
+DROP TABLE TwoFactor;
+DROP TABLE Lockout;
+DROP TABLE SigninFailure;
+DROP TABLE ApiKeyHotel;
+DROP TABLE ApiKey;
+DROP TABLE EmailVerification;
+DROP TABLE PasswordReset;
+DROP TABLE Session;
 DROP TABLE Booking;
+DROP TABLE Reservation;
 DROP TABLE Hotel;
 DROP TABLE Owner;
+DROP TABLE Admin;
 DROP TABLE Person;
 DROP TABLE RuleSet;
-DROP TABLE Manager;
 DROP TABLE Client;
 DROP TABLE MaintainedBy;
-DROP TABLE Receptionist;
 DROP TABLE Room;
 DROP TABLE ToClean;
-DROP TABLE Cleaner;
 DROP TABLE EmployedIn;
 DROP TABLE PhotoSet;
 DROP TABLE PhotoSetPhotos;
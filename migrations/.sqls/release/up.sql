@@ -1,24 +1,20 @@
+-- Needed to mix plain equality with range overlap in exclusion constraints
+CREATE EXTENSION IF NOT EXISTS btree_gist;
+
 CREATE TABLE Person (
   ID        SERIAL NOT NULL PRIMARY KEY,
   Name      varchar(255) NOT NULL, 
   Login     varchar(255) NOT NULL UNIQUE, 
   Email     varchar(255) NOT NULL UNIQUE, 
-  PassHash  varchar(64) NOT NULL 
-);
-
-CREATE TABLE Owner (
-  PersonID int4 NOT NULL PRIMARY KEY references Person(ID)
+  PassHash  varchar(255) NOT NULL,
+  EmailVerified boolean NOT NULL DEFAULT false
 );
 
-CREATE TABLE Manager (
+CREATE TABLE Admin (
   PersonID int4 NOT NULL PRIMARY KEY references Person(ID)
 );
 
-CREATE TABLE Receptionist (
-  PersonID int4 NOT NULL PRIMARY KEY references Person(ID)
-);
-
-CREATE TABLE Cleaner (
+CREATE TABLE Owner (
   PersonID int4 NOT NULL PRIMARY KEY references Person(ID)
 );
 
@@ -29,7 +25,7 @@ CREATE TABLE Client (
 
 CREATE TABLE RuleSet (
   ID              SERIAL NOT NULL PRIMARY KEY, 
-  ManagerPersonID int4 references Manager(PersonID) on delete set NULL, 
+  ManagerPersonID int4 references Person(ID) on delete set NULL, 
   Name            varchar(255) NOT NULL, 
   Body            text NOT NULL,
   IsDefault       boolean NOT NULL
@@ -87,6 +83,7 @@ CREATE TABLE Hotel (
 CREATE TABLE EmployedIn (
   PersonID int4 NOT NULL references Person(ID), 
   HotelID  int4 NOT NULL references Hotel(ID), 
+  Role     varchar(16) NOT NULL CHECK (Role IN ('Manager', 'Receptionist', 'Cleaner')),
   PRIMARY KEY (PersonID, HotelID)
 );
 
@@ -98,6 +95,14 @@ CREATE TABLE Room (
   PRIMARY KEY (HotelID, RoomNumber)
 );
 
+CREATE TABLE Reservation (
+  ID           SERIAL NOT NULL PRIMARY KEY,
+  -- Guest the whole group is booked under
+  LeadPersonID int4 NOT NULL references Person(ID),
+  HotelID      int4 NOT NULL references Hotel(ID),
+  CreatedAt    timestamp NOT NULL
+);
+
 CREATE TABLE Booking (
   ID             SERIAL NOT NULL PRIMARY KEY, 
   ClientPersonID int4 NOT NULL references Client(PersonID), 
@@ -108,7 +113,25 @@ CREATE TABLE Booking (
   DepartureTime  timestamp NOT NULL, 
   FullCost       int4 NOT NULL, 
   Paid           boolean NOT NULL, 
-  Cancelled      boolean NOT NULL
+  Cancelled      boolean NOT NULL,
+  CancelledBy    int4 references Person(ID),
+  CancelledAt    timestamp,
+  CancellationFee int4 NOT NULL DEFAULT 0,
+  -- Reserved, Confirmed, CheckedIn, CheckedOut, NoShow or Cancelled
+  Status         varchar(16) NOT NULL DEFAULT 'Reserved',
+  CheckedInAt    timestamp,
+  CheckedOutAt   timestamp,
+  -- Set for rooms booked together as a group
+  ReservationID  int4 references Reservation(ID),
+  CHECK (Cancelled = (Status = 'Cancelled')),
+  FOREIGN KEY (HotelID, RoomNumber) references Room(HotelID, RoomNumber),
+  CHECK (ArrivalTime < DepartureTime),
+  -- Room can't be booked twice for overlapping dates, cancelled bookings free it
+  CONSTRAINT Booking_NoOverlap EXCLUDE USING gist (
+    HotelID WITH =,
+    RoomNumber WITH =,
+    tsrange(ArrivalTime, DepartureTime) WITH &&
+  ) WHERE (NOT Cancelled)
 );
 
 CREATE TABLE Review (
@@ -124,7 +147,7 @@ CREATE TABLE Review (
 
 CREATE TABLE MaintainedBy (
   BookingID            int4 NOT NULL references Booking(ID), 
-  ReceptionistPersonID int4 NOT NULL references Receptionist(PersonID), 
+  ReceptionistPersonID int4 NOT NULL references Person(ID), 
   MaintainedAt         timestamp NOT NULL, 
   PRIMARY KEY (BookingID, ReceptionistPersonID)
 );
@@ -142,10 +165,238 @@ CREATE TABLE ToClean (
 
 CREATE TABLE AssignedCleaning (
   ToCleanID       int4 NOT NULL references ToClean(ID), 
-  CleanerPersonID int4 NOT NULL references Cleaner(PersonID),
+  CleanerPersonID int4 NOT NULL references Person(ID),
   PRIMARY KEY (ToCleanID, CleanerPersonID)
 );
 
+CREATE TABLE Session (
+  ID        SERIAL NOT NULL PRIMARY KEY,
+  Token     varchar(64) NOT NULL UNIQUE,
+  PersonID  int4 NOT NULL references Person(ID),
+  Created   timestamp NOT NULL,
+  LastSeen  timestamp NOT NULL,
+  Expires   timestamp NOT NULL,
+  UserAgent varchar(255),
+  -- Password was checked, but second factor is still awaited
+  Pending2FA boolean NOT NULL DEFAULT false
+);
+
+CREATE TABLE PasswordReset (
+  ID       SERIAL NOT NULL PRIMARY KEY,
+  Token    varchar(64) NOT NULL UNIQUE,
+  PersonID int4 NOT NULL references Person(ID),
+  Created  timestamp NOT NULL,
+  Expires  timestamp NOT NULL,
+  Used     boolean NOT NULL
+);
+
+CREATE TABLE EmailVerification (
+  ID       SERIAL NOT NULL PRIMARY KEY,
+  Token    varchar(64) NOT NULL UNIQUE,
+  PersonID int4 NOT NULL references Person(ID),
+  Email    varchar(255) NOT NULL,
+  Created  timestamp NOT NULL,
+  Expires  timestamp NOT NULL,
+  Used     boolean NOT NULL
+);
+
+CREATE TABLE ApiKey (
+  ID       SERIAL NOT NULL PRIMARY KEY,
+  PersonID int4 NOT NULL references Person(ID),
+  Name     varchar(64) NOT NULL,
+  KeyHash  varchar(64) NOT NULL UNIQUE,
+  Scopes   varchar(255) NOT NULL,
+  Created  timestamp NOT NULL,
+  LastUsed timestamp,
+  Revoked  boolean NOT NULL
+);
+
+CREATE TABLE ApiKeyHotel (
+  ApiKeyID int4 NOT NULL references ApiKey(ID),
+  HotelID  int4 NOT NULL references Hotel(ID),
+  PRIMARY KEY (ApiKeyID, HotelID)
+);
+
+-- Consecutive signin failures, keyed by "login:<login>" or "addr:<ip>"
+CREATE TABLE SigninFailure (
+  Key         varchar(255) NOT NULL PRIMARY KEY,
+  Failures    int4 NOT NULL,
+  LastFailure timestamp NOT NULL,
+  LockedUntil timestamp
+);
+
+CREATE TABLE Lockout (
+  ID          SERIAL NOT NULL PRIMARY KEY,
+  PersonID    int4 references Person(ID),
+  Login       varchar(255) NOT NULL,
+  Address     varchar(64) NOT NULL,
+  Failures    int4 NOT NULL,
+  LockedAt    timestamp NOT NULL,
+  LockedUntil timestamp NOT NULL
+);
+
+CREATE TABLE TwoFactor (
+  PersonID int4 NOT NULL PRIMARY KEY references Person(ID),
+  Secret   varchar(64) NOT NULL,
+  Enabled  boolean NOT NULL,
+  Created  timestamp NOT NULL,
+  -- Last accepted TOTP step, codes can't be replayed
+  LastStep int8
+);
+
+CREATE TABLE RecoveryCode (
+  ID       SERIAL NOT NULL PRIMARY KEY,
+  PersonID int4 NOT NULL references Person(ID),
+  CodeHash varchar(64) NOT NULL,
+  Used     boolean NOT NULL
+);
+
+CREATE TABLE AuthEvent (
+  ID        SERIAL NOT NULL PRIMARY KEY,
+  PersonID  int4 references Person(ID),
+  Login     varchar(255),
+  Kind      varchar(32) NOT NULL,
+  Address   varchar(64),
+  UserAgent varchar(255),
+  Created   timestamp NOT NULL
+);
+
+CREATE INDEX AuthEvent_PersonID_Created ON AuthEvent (PersonID, Created);
+
+-- Audit trail must not be rewritten, not even by the application.
+-- Only exception is wiping personal details of a deleted account, what happened and when stays
+CREATE OR REPLACE FUNCTION auth_event_append_only() RETURNS TRIGGER as $auth_event_append_only$
+BEGIN
+    IF TG_OP = 'UPDATE' AND new.ID = old.ID AND new.PersonID IS NOT DISTINCT FROM old.PersonID
+       AND new.Kind = old.Kind AND new.Created = old.Created
+       AND new.Login IS NULL AND new.Address IS NULL AND new.UserAgent IS NULL THEN
+        RETURN new;
+    END IF;
+
+    RAISE EXCEPTION 'AuthEvent is append-only';
+END;
+$auth_event_append_only$
+LANGUAGE 'plpgsql';
+
+CREATE TRIGGER auth_event_append_only BEFORE UPDATE OR DELETE ON AuthEvent
+    FOR EACH ROW EXECUTE PROCEDURE auth_event_append_only();
+
+CREATE TABLE BookingChange (
+  ID               SERIAL NOT NULL PRIMARY KEY,
+  BookingID        int4 NOT NULL references Booking(ID),
+  ChangedBy        int4 NOT NULL references Person(ID),
+  ChangedAt        timestamp NOT NULL,
+  OldRoomNumber    int4 NOT NULL,
+  OldArrivalTime   timestamp NOT NULL,
+  OldDepartureTime timestamp NOT NULL,
+  OldFullCost      int4 NOT NULL,
+  NewRoomNumber    int4 NOT NULL,
+  NewArrivalTime   timestamp NOT NULL,
+  NewDepartureTime timestamp NOT NULL,
+  NewFullCost      int4 NOT NULL,
+  -- Positive when guest has to pay extra
+  PriceDifference  int4 NOT NULL
+);
+
+CREATE TABLE BookingNight (
+  BookingID          int4 NOT NULL references Booking(ID),
+  Night              date NOT NULL,
+  BaseRate           int4 NOT NULL,
+  DiscountPercentage int4 NOT NULL,
+  Price              int4 NOT NULL,
+  PRIMARY KEY (BookingID, Night)
+);
+
+CREATE TABLE LedgerEntry (
+  ID         SERIAL NOT NULL PRIMARY KEY,
+  BookingID  int4 NOT NULL references Booking(ID),
+  -- Charge, Payment, Deposit or Refund
+  Kind       varchar(16) NOT NULL,
+  -- Charges are negative when booking got cheaper, everything else is positive
+  Amount     int4 NOT NULL,
+  -- Cash, Card or Online, NULL for charges
+  Method     varchar(16),
+  -- Gateway or terminal reference of the transaction
+  Reference  varchar(255),
+  -- Online payment given back by this refund
+  RefundOf   int4 references LedgerEntry(ID),
+  RecordedBy int4 references Person(ID),
+  Created    timestamp NOT NULL,
+  CHECK (Kind = 'Charge' OR Amount > 0)
+);
+
+CREATE INDEX LedgerEntry_BookingID ON LedgerEntry (BookingID);
+
+-- Money that changed hands is never edited, mistakes are corrected with new entries
+CREATE OR REPLACE FUNCTION ledger_entry_append_only() RETURNS TRIGGER as $ledger_entry_append_only$
+BEGIN
+    RAISE EXCEPTION 'LedgerEntry is append-only';
+END;
+$ledger_entry_append_only$
+LANGUAGE 'plpgsql';
+
+CREATE TRIGGER ledger_entry_append_only BEFORE UPDATE OR DELETE ON LedgerEntry
+    FOR EACH ROW EXECUTE PROCEDURE ledger_entry_append_only();
+
+-- Positive while guest still owes money
+CREATE OR REPLACE FUNCTION booking_balance(booking_id int4) RETURNS int4 as $booking_balance$
+BEGIN
+    RETURN (SELECT COALESCE(sum(CASE WHEN LedgerEntry.Kind IN ('Charge', 'Refund')
+                                     THEN LedgerEntry.Amount
+                                     ELSE -LedgerEntry.Amount END), 0)
+            FROM LedgerEntry WHERE LedgerEntry.BookingID = booking_id);
+END;
+$booking_balance$
+LANGUAGE 'plpgsql';
+
+-- Trigger to keep Booking.Paid derived from the balance
+CREATE OR REPLACE FUNCTION update_booking_paid() RETURNS TRIGGER as $update_booking_paid$
+BEGIN
+    UPDATE Booking SET Paid = (booking_balance(new.BookingID) <= 0) WHERE ID = new.BookingID;
+    RETURN new;
+END;
+$update_booking_paid$
+LANGUAGE 'plpgsql';
+
+CREATE TRIGGER update_booking_paid AFTER INSERT ON LedgerEntry
+    FOR EACH ROW EXECUTE PROCEDURE update_booking_paid();
+
+-- Trigger to charge whatever guest owes for the booking, cancelled one costs only the fee
+CREATE OR REPLACE FUNCTION record_booking_charge() RETURNS TRIGGER as $record_booking_charge$
+DECLARE
+old_due int4;
+new_due int4;
+BEGIN
+    old_due := 0;
+    IF TG_OP = 'UPDATE' THEN
+        old_due := CASE WHEN old.Cancelled THEN old.CancellationFee ELSE old.FullCost END;
+    END IF;
+    new_due := CASE WHEN new.Cancelled THEN new.CancellationFee ELSE new.FullCost END;
+
+    IF new_due <> old_due THEN
+        INSERT INTO LedgerEntry (BookingID, Kind, Amount, Method, Reference, RecordedBy, Created)
+        VALUES (new.ID, 'Charge', new_due - old_due, NULL, NULL, NULL, now()::timestamp);
+    END IF;
+
+    RETURN new;
+END;
+$record_booking_charge$
+LANGUAGE 'plpgsql';
+
+CREATE TRIGGER record_booking_charge AFTER INSERT OR UPDATE OF FullCost, Cancelled, CancellationFee,
+    RoomNumber, ArrivalTime, DepartureTime ON Booking
+    FOR EACH ROW EXECUTE PROCEDURE record_booking_charge();
+
+CREATE TABLE Invoice (
+  ID        SERIAL NOT NULL PRIMARY KEY,
+  HotelID   int4 NOT NULL references Hotel(ID),
+  -- Sequential within the hotel, numbers are never skipped
+  Number    int4 NOT NULL,
+  BookingID int4 NOT NULL UNIQUE references Booking(ID),
+  IssuedAt  timestamp NOT NULL,
+  UNIQUE (HotelID, Number)
+);
+
 -- Trigger to auto add registered users to Client table
 CREATE OR REPLACE FUNCTION auto_add_client() RETURNS TRIGGER as $emp_auto_add_client$
 DECLARE
@@ -165,13 +416,14 @@ CREATE OR REPLACE FUNCTION insert_booking_and_return_id(ClientPersonID int4,
                                                         RoomNumber     int4, 
                                                         BookingTime    timestamp, 
                                                         ArrivalTime    timestamp, 
-                                                        DepartureTime  timestamp) 
+                                                        DepartureTime  timestamp,
+                                                        ReservationID  int4) 
                                                         RETURNS int4 as $insert_booking_and_return_id$
 DECLARE
 new_id int4;
 BEGIN
-    INSERT INTO Booking (ClientPersonID, HotelID, RoomNumber, BookingTime, ArrivalTime, DepartureTime, FullCost, Paid, Cancelled)
-    VALUES(ClientPersonID, HotelID, RoomNumber, BookingTime, ArrivalTime, DepartureTime, 0, false, false) 
+    INSERT INTO Booking (ClientPersonID, HotelID, RoomNumber, BookingTime, ArrivalTime, DepartureTime, FullCost, Paid, Cancelled, ReservationID)
+    VALUES(ClientPersonID, HotelID, RoomNumber, BookingTime, ArrivalTime, DepartureTime, 0, false, false, ReservationID) 
     RETURNING id into new_id;
     RETURN new_id;
 END;
@@ -199,46 +451,72 @@ END;
 $insert_hotel_and_return_id$
 LANGUAGE 'plpgsql';
 
--- Calculate cost
-CREATE OR REPLACE FUNCTION room_cost(room_level int4, hotel_id int4, client_id int4) RETURNS int4 as $room_cost$
+-- Price of every night of a stay, the booking itself doesn't count towards client level
+CREATE OR REPLACE FUNCTION stay_nights(booking_id int4, hotel_id int4, room_number int4, client_id int4,
+                                       arrival timestamp, departure timestamp)
+RETURNS TABLE (Night date, BaseRate int4, DiscountPercentage int4, Price int4) as $stay_nights$
 DECLARE
 per_night int4;
 discount int4;
 BEGIN
-    -- Get cost per-night for booked room level
-    SELECT INTO per_night RoomLevel.PerNight FROM RoomLevel, Hotel
-    WHERE Hotel.ID = hotel_id and Hotel.RuleSetID = RoomLevel.RuleSetID
-      AND RoomLevel.Level = room_level;
+    -- Get cost per-night for level of booked room
+    SELECT INTO per_night RoomLevel.PerNight FROM Room, RoomLevel, Hotel
+    WHERE Room.HotelID = hotel_id and Room.RoomNumber = room_number
+      AND Hotel.ID = hotel_id and RoomLevel.RuleSetID = Hotel.RuleSetID
+      AND RoomLevel.Level = Room.RoomLevel;
 
-    -- Get discount
-    SELECT INTO discount min(ClientLevel.DiscountPercentage) FROM ClientLevel, Hotel
+    -- Get discount of the highest level client has reached
+    SELECT INTO discount max(ClientLevel.DiscountPercentage) FROM ClientLevel, Hotel
     WHERE Hotel.ID = hotel_id and ClientLevel.RuleSetID = Hotel.RuleSetID
-      AND ClientLevel.BookingsAmount > (SELECT count(*) FROM Booking 
-                                        WHERE Booking.ClientPersonID = client_id);
+      AND ClientLevel.BookingsAmount <= (SELECT count(*) FROM Booking
+                                         WHERE Booking.ClientPersonID = client_id
+                                           AND Booking.ID <> booking_id
+                                           AND NOT Booking.Cancelled);
+
+    per_night := COALESCE(per_night, 0);
+    discount := COALESCE(discount, 0);
 
-    RETURN ((100 - discount)/100) * 
-                    per_night;
+    -- Departure day isn't paid for, stay within a single day is one night
+    RETURN QUERY
+    SELECT stay_day::date, per_night, discount, per_night * (100 - discount) / 100
+    FROM generate_series(arrival::date,
+                         GREATEST(departure::date - 1, arrival::date),
+                         interval '1 day') AS stay_day;
 END;
-$room_cost$
+$stay_nights$
 LANGUAGE 'plpgsql';
 
--- Trigger to auto calculate the cost on Booking insert
+-- Trigger to auto calculate the cost on Booking insert and change of stay
 CREATE OR REPLACE FUNCTION calculate_booking_cost() RETURNS TRIGGER as $calculate_booking_cost$
 DECLARE
-room_level int4;
 BEGIN
-    SELECT INTO room_level RoomLevel.Level FROM Room, RoomLevel
-    WHERE Room.HotelID = new.HotelID and Room.RoomNumber = new.RoomNumber;
-
-    -- Calculate number of nights
-    -- Set cost
-    new.FullCost := (SELECT room_cost(room_level, new.HotelID, new.ClientPersonID)); 
-                  -- * EXTRACT(DAY FROM (new.ArrivalTime, new.DepartureTime)); 
+    new.FullCost := (SELECT COALESCE(sum(stay.Price), 0)
+                     FROM stay_nights(new.ID, new.HotelID, new.RoomNumber, new.ClientPersonID,
+                                      new.ArrivalTime, new.DepartureTime) AS stay);
 
     RETURN new;
 END;
 $calculate_booking_cost$
 LANGUAGE 'plpgsql';
 
-CREATE TRIGGER calculate_booking_cost BEFORE INSERT ON Booking
-    FOR EACH ROW EXECUTE PROCEDURE calculate_booking_cost(); 
\ No newline at end of file
+CREATE TRIGGER calculate_booking_cost BEFORE INSERT OR UPDATE OF RoomNumber, ArrivalTime, DepartureTime ON Booking
+    FOR EACH ROW EXECUTE PROCEDURE calculate_booking_cost();
+
+-- Trigger to keep per-night breakdown in line with the stay, runs after Booking row exists
+CREATE OR REPLACE FUNCTION store_booking_nights() RETURNS TRIGGER as $store_booking_nights$
+DECLARE
+BEGIN
+    DELETE FROM BookingNight WHERE BookingNight.BookingID = new.ID;
+
+    INSERT INTO BookingNight (BookingID, Night, BaseRate, DiscountPercentage, Price)
+    SELECT new.ID, stay.Night, stay.BaseRate, stay.DiscountPercentage, stay.Price
+    FROM stay_nights(new.ID, new.HotelID, new.RoomNumber, new.ClientPersonID,
+                     new.ArrivalTime, new.DepartureTime) AS stay;
+
+    RETURN new;
+END;
+$store_booking_nights$
+LANGUAGE 'plpgsql';
+
+CREATE TRIGGER store_booking_nights AFTER INSERT OR UPDATE OF RoomNumber, ArrivalTime, DepartureTime ON Booking
+    FOR EACH ROW EXECUTE PROCEDURE store_booking_nights();
@@ -1,12 +1,13 @@
 -- Initial users setup
+INSERT INTO Person(Login, Name, Email, PassHash) VALUES('admin', 'Admin', 'admin@dmd.ru', '0');
 INSERT INTO Person(Login, Name, Email, PassHash) VALUES('owner', 'Owner', 'owner@dmd.ru', '0');
 INSERT INTO Person(Login, Name, Email, PassHash) VALUES('manager', 'Manager', 'manager@dmd.ru', '0');
 INSERT INTO Person(Login, Name, Email, PassHash) VALUES('receptionist', 'Receptionist', 'receptionist@dmd.ru', '0');
 INSERT INTO Person(Login, Name, Email, PassHash) VALUES('cleaner', 'Cleaner', 'cleaner@dmd.ru', '0');
 
+INSERT INTO Admin(PersonID) VALUES ((SELECT Person.ID FROM Person WHERE Person.Login = 'admin'));
 INSERT INTO Owner(PersonID) VALUES ((SELECT Person.ID FROM Person WHERE Person.Login = 'owner'));
-INSERT INTO Manager(PersonID) VALUES ((SELECT Person.ID FROM Person WHERE Person.Login = 'manager'));
-INSERT INTO Receptionist(PersonID) VALUES ((SELECT Person.ID FROM Person WHERE Person.Login = 'receptionist'));
-INSERT INTO Cleaner(PersonID) VALUES ((SELECT Person.ID FROM Person WHERE Person.Login = 'cleaner'));
+
+-- Staff roles are scoped to hotels, hire with PUT /api/hotel/:id/employee/
 
 -- The other data is populated with application
\ No newline at end of file
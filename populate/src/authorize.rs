@@ -25,7 +25,7 @@ impl<'a> SignedRequest for RequestBuilder<'a> {
 pub fn signin_with(login: &str, passwd: &str) -> (String, Roles) {
     let client = hyper::Client::new();
     let mut res = client.post("http://localhost:8080/api/signin/")
-        .body(&format!("{{ \"Login\":\"{}\", \"PassHash\":\"{}\"}}", login, passwd))
+        .body(&format!("{{ \"Login\":\"{}\", \"Password\":\"{}\"}}", login, passwd))
         .send()
         .unwrap();
     
@@ -37,17 +37,18 @@ pub fn signin_with(login: &str, passwd: &str) -> (String, Roles) {
         .last()
         .unwrap();
 
-    let roles: Roles = json::decode(&resp_body).unwrap();
+    let signin: SigninResponse = json::decode(&resp_body).unwrap();
     assert_eq!(res.status, StatusCode::Ok);
+    assert_eq!(signin.Token, token.value);
 
-    (token.value.to_owned(), roles)
+    (signin.Token, signin.Roles.unwrap())
 }
 
 pub fn signup_with(login: &str, name: &str, email: &str, passhash: &str) -> String {
     let client = hyper::Client::new();
     let mut res = client.post("http://localhost:8080/api/signup/")
         .body(&format!("{{ \"Login\":\"{}\", \"Name\":\"{}\", \"Email\":\"{}\", \
-                 \"PassHash\":\"{}\"}}", login, name, email, passhash))
+                 \"Password\":\"{}\"}}", login, name, email, passhash))
         .send()
         .unwrap();
     
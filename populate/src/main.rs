@@ -10,17 +10,19 @@ mod response;
 mod schema;
 
 use rustc_serialize::json;
-use response::random_str;
+use response::{random_str, random_email};
 use chrono::NaiveDateTime;
+use std::sync::mpsc;
 
 fn main() {
+    let (admin_token, _) = authorize::signin_with("admin", "0");
     let (owner_token, _) = authorize::signin_with("owner", "0");
     
     let cities = ["Abakan", "Amsterdam", "Athens", "Antalia", "Rome", "Budapest", "Helsinki", "Oslo", "Stockholm", "Copenhagen", "Moscow", "Kazan", "Innopolis", "Samara", "Saint Petersburg", "Madrid", "Paris", "London", "New York", "Berlin", "Warsaw", "Vienna"];
     let cities_cnt = cities.len() as i32;
 
     for city in &cities {
-        insert_city(city);
+        insert_city(&admin_token, city);
     }
 
     let pool = threadpool::ThreadPool::new(16);
@@ -33,26 +35,35 @@ fn main() {
             Stars: Some((rand::random::<i32>() % 5).abs()),
         });
         
+        let (room_tx, room_rx) = mpsc::channel();
+
         for r in 0..1000 {
             let owner_token = owner_token.clone();
+            let room_tx = room_tx.clone();
             pool.execute(move || {
                 insert_room(&owner_token, i+1, schema::NewRoom {
                     RoomNumber: r, 
                     RoomLevel: (rand::random::<i32>() % 4).abs(),
                     PhotoSetID: None
                 });
+                room_tx.send(()).unwrap();
             });
         }
 
+        // Rooms must exist before they can be booked
+        for _ in 0..1000 {
+            room_rx.recv().unwrap();
+        }
+
         for b in 0..1000 {
             pool.execute(move || {
-                let client_token = authorize::signup_with(&random_str(), &random_str(), &random_str(), &random_str());
+                let client_token = authorize::signup_with(&random_str(), &random_str(), &random_email(), &random_str());
                 insert_booking(&client_token, schema::NewBooking {
                     ClientPersonID: None,
                     HotelID: Some(i+1),
                     RoomNumber: b,
                     ArrivalTime: chrono::UTC::now().naive_local(),
-                    DepartureTime: chrono::UTC::now().naive_local(),
+                    DepartureTime: chrono::UTC::now().naive_local() + chrono::Duration::days(1),
                 });
             });
         }
@@ -106,10 +117,11 @@ fn insert_booking(token: &str, booking: schema::NewBooking) {
     }
 }
 
-fn insert_city(name: &str) {
+fn insert_city(token: &str, name: &str) {
     let client = hyper::Client::new();
     let mut res = client.put("http://localhost:8080/api/city/")
           .body(&format!("{{\"Name\":\"{}\"}}", name))
+          .sign(token.to_owned())
           .send()
           .unwrap();
 
@@ -19,3 +19,7 @@ pub fn random_str() -> String {
         .collect()
 }
 
+pub fn random_email() -> String {
+    format!("{}@example.com", random_str())
+}
+
@@ -3,7 +3,7 @@ use chrono::NaiveDateTime;
 #[derive(Debug, RustcDecodable)]
 pub struct SigninData {
     pub Login: String,
-    pub PassHash: String,
+    pub Password: String,
 }
 
 #[derive(Debug, RustcDecodable)]
@@ -11,7 +11,7 @@ pub struct SignupData {
     pub Login: String,
     pub Name: String,
     pub Email: String,
-    pub PassHash: String,
+    pub Password: String,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, RustcEncodable, RustcDecodable)]
@@ -25,12 +25,19 @@ pub struct Roles {
     pub EmployedIn: Option<Vec<i32>>,
 }
 
+#[derive(Debug, Clone, RustcEncodable, RustcDecodable)]
+pub struct SigninResponse {
+    pub Token: String,
+    pub Roles: Option<Roles>,
+    pub Pending2FA: bool,
+}
+
 #[derive(Debug, RustcEncodable, RustcDecodable)]
 pub struct UpdateAccountInfoData {
     pub NewName: Option<String>,
     pub NewEmail: Option<String>,
-    pub OldPassHash: Option<String>,
-    pub NewPassHash: Option<String>,
+    pub OldPassword: Option<String>,
+    pub NewPassword: Option<String>,
 }
 
 #[derive(Debug, RustcEncodable, RustcDecodable)]
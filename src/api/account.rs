@@ -3,22 +3,39 @@ use hyper::status::StatusCode;
 use iron::prelude::*;
 use params::{Params, FromValue};
 use postgres::types::ToSql;
+use postgres::Connection;
 use std::str::FromStr;
 use std::i32;
+use chrono;
 
 use super::request_body;
-use ::api::authorization::Authorizer;
+use ::api::authorization::{Authorizer, new_token};
+use ::api::session::{SessionStore, PgSessionStore};
+use ::api::password;
+use ::api::lockout;
+use ::api::audit;
+use ::api::audit::{AuthEventKind, Origin};
+use ::api::validation;
 use ::proto::error::*;
 use ::proto::response::*;
 use ::proto::schema::*;
 use ::db::schema::*;
 use ::db::schemaext::*;
 use ::db::*;
+use ::mailer;
+use ::mailer::Mail;
+use ::config;
 
+// Signup never accepts ':' in logins, so anonymized ones can't collide
+const DELETED_LOGIN_PREFIX: &'static str = "deleted:";
+const DELETED_NAME: &'static str = "Deleted account";
+// Reserved TLD, mail to it is never delivered
+const DELETED_EMAIL_DOMAIN: &'static str = "deleted.invalid";
 
 pub fn get_bookings(req: &mut Request) -> IronResult<Response> {
     let conn = get_db_connection();
     let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::ReadBookings)?;
 
     let ofst = req.get_ref::<Params>()
         .unwrap()
@@ -59,7 +76,7 @@ pub fn get_account_info(req: &mut Request) -> IronResult<Response> {
     info!("request GET /account/ {{ id: {} }}", user.id);
 
     let info = conn.query(&Client::select_builder()
-                   .columns("ID,Login,Name,Email")
+                   .columns("ID,Login,Name,Email,EmailVerified")
                    .from_tables("Person")
                    .filter("ID = $1")
                    .build(),
@@ -76,36 +93,39 @@ pub fn get_account_info(req: &mut Request) -> IronResult<Response> {
 pub fn update_account_info(req: &mut Request) -> IronResult<Response> {
     let conn = get_db_connection();
     let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_session()?;
     let upd_info_data: UpdateAccountInfoData = request_body(req)?;
 
     info!("request POST /account/ {{ {:?} }}", upd_info_data);
 
-    // Vector to store values that need an update
-    let mut update = Person::update_builder().filter(format!("ID={}", user.id));
-    let mut values: Vec<&ToSql> = Vec::with_capacity(3);
-
-    // If user wants to update password, both OldPassHash and NewPassHash must be set
-    if let Some(new_hash) = upd_info_data.NewPassHash.as_ref() {
-        if let Some(old_hash) = upd_info_data.OldPassHash.as_ref() {
-            // Try to query user with received old_hash
-            let count = conn.query(&Person::select_builder()
-                           .filter("ID = $1 and PassHash = $2")
-                           .build(),
-                       &[&user.id, &old_hash])
-                .unwrap()
-                .into_iter()
-                .count();
-            // If count is non-zero, there password is right
-            if count != 0 {
-                values.push(new_hash);
-                update = update.set("PassHash")
-            } else {
-                return Err(OldPasswordIsInvalidError::from_str("Old password is invalid").into());
+    validation::fields(vec![upd_info_data.NewName
+                                .as_ref()
+                                .and_then(|name| validation::name(name)),
+                            upd_info_data.NewEmail
+                                .as_ref()
+                                .and_then(|email| validation::email("NewEmail", email)),
+                            upd_info_data.NewPassword
+                                .as_ref()
+                                .and_then(|password| {
+                                    validation::password("NewPassword", password)
+                                })])?;
+
+    // If user wants to update password, both OldPassword and NewPassword must be set
+    if upd_info_data.NewPassword.is_some() {
+        match upd_info_data.OldPassword.as_ref() {
+            Some(old_password) => {
+                if !Authorizer::check_password(&conn, user.id, old_password) {
+                    return Err(OldPasswordIsInvalidError::from_str("Old password is invalid")
+                        .into());
+                }
             }
-        } else {
-            return Err(IncompleteDataError::from_str("Missing OldPassHash").into());
+            None => return Err(IncompleteDataError::from_str("Missing OldPassword").into()),
         }
-    };
+    }
+
+    // Vector to store values that need an update
+    let mut update = Person::update_builder().filter(format!("ID={}", user.id));
+    let mut values: Vec<&ToSql> = Vec::with_capacity(2);
 
     if let Some(new_name) = upd_info_data.NewName.as_ref() {
         update = update.set("Name");
@@ -115,15 +135,475 @@ pub fn update_account_info(req: &mut Request) -> IronResult<Response> {
     if let Some(new_email) = upd_info_data.NewEmail.as_ref() {
         update = update.set("Email");
         values.push(new_email);
+        update = update.set("EmailVerified");
+        values.push(&false);
     }
 
-    // Early exit if we got empty json
-    if values.is_empty() {
+    // Early exit if there is nothing to update
+    if values.is_empty() && upd_info_data.NewPassword.is_none() {
         return Ok(Response::with(StatusCode::Ok));
     }
 
-    conn.execute(&update.build(), &values)
+    // Password must not change when the rest of the update is rejected
+    let trans = conn.transaction().unwrap();
+
+    if !values.is_empty() {
+        trans.execute(&update.build(), &values)
+            .map_err(validation::person_error)?;
+    }
+
+    if let Some(new_password) = upd_info_data.NewPassword.as_ref() {
+        Authorizer::set_password(&trans, user.id, new_password);
+    }
+
+    trans.commit().unwrap();
+
+    if upd_info_data.NewPassword.is_some() {
+        audit::record(&*conn,
+                      AuthEventKind::PasswordChanged,
+                      Some(user.id),
+                      None,
+                      &Origin::of(req));
+    }
+
+    if let Some(new_email) = upd_info_data.NewEmail {
+        send_email_verification(&conn, user.id, new_email);
+    }
+
+    Ok(Response::with(StatusCode::Ok))
+}
+
+pub fn export_account(req: &mut Request) -> IronResult<Response> {
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_session()?;
+
+    info!("request GET /account/export {{ id: {} }}", user.id);
+
+    let profile = conn.query(&Person::select_builder()
+                   .columns("ID,Login,Name,Email,EmailVerified")
+                   .filter("ID = $1")
+                   .build(),
+               &[&user.id])
+        .unwrap()
+        .into_iter()
+        .map(AccountInfo::from)
+        .last()
+        .unwrap();
+
+    let bookings = conn.query(&Booking::select_builder()
+                   .filter("ClientPersonID = $1")
+                   .order_by("BookingTime")
+                   .build(),
+               &[&user.id])
+        .unwrap()
+        .into_iter()
+        .map(Booking::from)
+        .collect::<Vec<Booking>>();
+
+    let reviews = conn.query(&SelectQueryBuilder::default()
+                   .columns("Review.ID, Review.BookingID, COALESCE(Review.Body, '') as Body, \
+                             Review.LocationRate, Review.CleanlinessRate, Review.ServiceRate, \
+                             Review.ValueForMoneyRate, Review.CreatedAt")
+                   .from_tables("Review, Booking")
+                   .filter("Review.BookingID = Booking.ID and Booking.ClientPersonID = $1")
+                   .order_by("Review.CreatedAt")
+                   .build(),
+               &[&user.id])
+        .unwrap()
+        .into_iter()
+        .map(Review::from)
+        .collect::<Vec<Review>>();
+
+    let maintained = conn.query(&MaintainedBy::select_builder()
+                   .filter("ReceptionistPersonID = $1")
+                   .order_by("MaintainedAt")
+                   .build(),
+               &[&user.id])
+        .unwrap()
+        .into_iter()
+        .map(MaintainedBy::from)
+        .collect::<Vec<MaintainedBy>>();
+
+    let mut response = AccountExport {
+            Profile: profile,
+            Bookings: bookings,
+            Reviews: reviews,
+            MaintainedBookings: maintained,
+            ExportedAt: chrono::UTC::now().naive_local(),
+        }
+        .as_response();
+
+    // Browsers save the archive instead of rendering it
+    response.headers.set_raw("Content-Disposition",
+                             vec![format!("attachment; filename=\"account-{}.json\"", user.id)
+                                      .into_bytes()]);
+
+    Ok(response)
+}
+
+// Person row stays, so bookings, reviews and receptionist history still add up
+// in hotel accounting, but nothing left in it identifies the person
+pub fn delete_account(req: &mut Request) -> IronResult<Response> {
+    let deletion: AccountDeletion = request_body(req)?;
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_session()?;
+
+    info!("request DELETE /account/ {{ id: {} }}", user.id);
+
+    if !Authorizer::check_password(&conn, user.id, &deletion.Password) {
+        return Err(OldPasswordIsInvalidError::from_str("Password is invalid").into());
+    }
+
+    let owns_hotels = !conn.query(&Hotel::select_builder()
+                   .columns("ID")
+                   .filter("OwnerPersonID = $1")
+                   .build(),
+               &[&user.id])
+        .unwrap()
+        .is_empty();
+
+    if owns_hotels {
+        return Err(NotAuthorizedError::from_str("Owned hotels must be transferred before \
+                                                 deleting the account")
+            .into());
+    }
+
+    let login: String = conn.query(&Person::select_builder()
+                       .columns("Login")
+                       .filter("ID = $1")
+                       .build(),
+                   &[&user.id])
+        .unwrap()
+        .get(0)
+        .get("Login");
+
+    let trans = conn.transaction().unwrap();
+
+    // Nobody knows the password behind a hash of a fresh random token
+    trans.execute(&Person::update_builder()
+                     .set("Login")
+                     .set("Name")
+                     .set("Email")
+                     .set("EmailVerified")
+                     .set("PassHash")
+                     .filter("ID = $6")
+                     .build(),
+                 &[&format!("{}{}", DELETED_LOGIN_PREFIX, user.id),
+                   &DELETED_NAME,
+                   &format!("{}{}@{}", DELETED_LOGIN_PREFIX, user.id, DELETED_EMAIL_DOMAIN),
+                   &false,
+                   &password::hash(&new_token()),
+                   &user.id])
+        .unwrap();
+
+    trans.execute(&ApiKey::update_builder()
+                     .set("Revoked")
+                     .filter("PersonID = $2")
+                     .build(),
+                 &[&true, &user.id])
+        .unwrap();
+
+    for table in &["Session",
+                   "PasswordReset",
+                   "EmailVerification",
+                   "RecoveryCode",
+                   "TwoFactor",
+                   "Lockout",
+                   "EmployedIn",
+                   "Owner",
+                   "Admin"] {
+        trans.execute(&DeleteQueryBuilder::default()
+                         .from_tables(table)
+                         .filter("PersonID = $1")
+                         .build(),
+                     &[&user.id])
+            .unwrap();
+    }
+
+    trans.execute(&SigninFailure::delete_builder()
+                     .filter("Key = $1")
+                     .build(),
+                 &[&lockout::login_key(&login)])
         .unwrap();
 
+    audit::record(&trans,
+                  AuthEventKind::AccountDeleted,
+                  Some(user.id),
+                  None,
+                  &Origin::of(req));
+    audit::scrub(&trans, user.id, &login);
+
+    trans.commit().unwrap();
+
+    Authorizer::invalidate_roles(user.id);
+
     Ok(Response::with(StatusCode::Ok))
 }
+
+pub fn get_sessions(req: &mut Request) -> IronResult<Response> {
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_session()?;
+    let token = Authorizer::request_token(req)?;
+
+    info!("request GET /account/sessions/ {{ id: {} }}", user.id);
+
+    let sessions = PgSessionStore::new(&conn)
+        .list(user.id)
+        .into_iter()
+        .map(|session| {
+            SessionInfo {
+                ID: session.ID,
+                Created: session.Created,
+                LastSeen: session.LastSeen,
+                Expires: session.Expires,
+                UserAgent: session.UserAgent,
+                Current: session.Token == token,
+            }
+        })
+        .collect::<Vec<SessionInfo>>();
+
+    Ok(sessions.as_response())
+}
+
+pub fn revoke_session(req: &mut Request) -> IronResult<Response> {
+    let session_id = req.extensions
+        .get::<Router>()
+        .unwrap()
+        .find("id")
+        .map(|s| i32::from_str(s).unwrap())
+        .expect("No Session ID in request");
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_session()?;
+
+    info!("request DELETE /account/sessions/{} {{ id: {} }}",
+          session_id,
+          user.id);
+
+    if !PgSessionStore::new(&conn).revoke_by_id(user.id, session_id) {
+        return Err(NotFoundError::from_str(format!("No session with id {}", session_id)).into());
+    }
+
+    audit::record(&*conn,
+                  AuthEventKind::SessionRevoked,
+                  Some(user.id),
+                  None,
+                  &Origin::of(req));
+
+    Ok(Response::with(StatusCode::Ok))
+}
+
+pub fn request_password_reset(req: &mut Request) -> IronResult<Response> {
+    let reset_req: PasswordResetRequest = request_body(req)?;
+
+    info!("request POST /account/password-reset/ {{ {:?} }}", reset_req);
+
+    let (filter, key) = match (reset_req.Login, reset_req.Email) {
+        (Some(login), _) => ("Login = $1", login),
+        (None, Some(email)) => ("Email = $1", email),
+        (None, None) => {
+            return Err(IncompleteDataError::from_str("Either Login or Email must be set").into())
+        }
+    };
+
+    let conn = get_db_connection();
+    let person = conn.query(&Person::select_builder()
+                   .filter(filter)
+                   .build(),
+               &[&key])
+        .unwrap()
+        .into_iter()
+        .map(Person::from)
+        .last();
+
+    // Respond the same way whether person exists or not,
+    // so the endpoint can't be used to probe for accounts
+    let person = match person {
+        Some(person) => person,
+        None => return Ok(Response::with(StatusCode::Ok)),
+    };
+
+    let now = chrono::UTC::now().naive_local();
+
+    // Only the latest reset token stays valid
+    conn.execute(&PasswordReset::update_builder()
+                     .filter("PersonID = $2 and Used = false")
+                     .set("Used")
+                     .build(),
+                 &[&true, &person.ID])
+        .unwrap();
+
+    let reset = PasswordReset {
+        ID: 0,
+        Token: new_token(),
+        PersonID: person.ID,
+        Created: now,
+        Expires: now + chrono::Duration::seconds(*config::PASSWORD_RESET_TTL),
+        Used: false,
+    };
+
+    conn.execute(&PasswordReset::insert_query(), &reset.insert_args())
+        .unwrap();
+
+    mailer::send(Mail {
+        to: person.Email,
+        subject: "Password reset".to_owned(),
+        body: format!("Hello, {}!\r\n\r\n\
+                       Somebody requested a password reset for your account \"{}\".\r\n\
+                       Use the following token to set a new password, \
+                       it expires at {} UTC:\r\n\r\n{}\r\n\r\n\
+                       If it wasn't you, just ignore this mail.",
+                      person.Name,
+                      person.Login,
+                      reset.Expires.format("%Y-%m-%d %H:%M"),
+                      reset.Token),
+    });
+
+    Ok(Response::with(StatusCode::Ok))
+}
+
+pub fn confirm_password_reset(req: &mut Request) -> IronResult<Response> {
+    let confirm: PasswordResetConfirm = request_body(req)?;
+
+    info!("request POST /account/password-reset/confirm");
+
+    // Token is only spent on a password that would be accepted
+    validation::fields(vec![validation::password("NewPassword", &confirm.NewPassword)])?;
+
+    let conn = get_db_connection();
+
+    // Claim the token atomically, so it can't be used twice
+    let person_id = conn.query(&UpdateQueryBuilder::with_template("UPDATE $table SET $columns \
+                                                                   $where_clause RETURNING \
+                                                                   PersonID;")
+                   .table("PasswordReset")
+                   .set("Used")
+                   .filter("Token = $2 and Used = false and Expires > $3")
+                   .build(),
+               &[&true, &confirm.Token, &chrono::UTC::now().naive_local()])
+        .unwrap()
+        .into_iter()
+        .map(|row| row.get::<_, i32>("PersonID"))
+        .last()
+        .ok_or(box InvalidTokenError::from_str("Invalid or expired reset token") as Box<ApiError>)?;
+
+    Authorizer::set_password(&*conn, person_id, &confirm.NewPassword);
+
+    // Whoever knew the old password must not stay signed in
+    PgSessionStore::new(&conn).revoke_all(person_id);
+
+    audit::record(&*conn,
+                  AuthEventKind::PasswordReset,
+                  Some(person_id),
+                  None,
+                  &Origin::of(req));
+
+    Ok(Response::with(StatusCode::Ok))
+}
+
+pub fn request_email_verification(req: &mut Request) -> IronResult<Response> {
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_session()?;
+
+    info!("request POST /account/verify-email/ {{ id: {} }}", user.id);
+
+    let person = conn.query(&Person::select_builder()
+                   .filter("ID = $1")
+                   .build(),
+               &[&user.id])
+        .unwrap()
+        .into_iter()
+        .map(Person::from)
+        .last()
+        .unwrap();
+
+    if !person.EmailVerified {
+        send_email_verification(&conn, person.ID, person.Email);
+    }
+
+    Ok(Response::with(StatusCode::Ok))
+}
+
+pub fn confirm_email_verification(req: &mut Request) -> IronResult<Response> {
+    let confirm: EmailVerificationConfirm = request_body(req)?;
+
+    info!("request POST /account/verify-email/confirm");
+
+    let conn = get_db_connection();
+
+    let (person_id, email) = conn.query(&UpdateQueryBuilder::with_template("UPDATE $table SET \
+                                                                            $columns \
+                                                                            $where_clause \
+                                                                            RETURNING \
+                                                                            PersonID, Email;")
+                   .table("EmailVerification")
+                   .set("Used")
+                   .filter("Token = $2 and Used = false and Expires > $3")
+                   .build(),
+               &[&true, &confirm.Token, &chrono::UTC::now().naive_local()])
+        .unwrap()
+        .into_iter()
+        .map(|row| (row.get::<_, i32>("PersonID"), row.get::<_, String>("Email")))
+        .last()
+        .ok_or(box InvalidTokenError::from_str("Invalid or expired verification token") as
+               Box<ApiError>)?;
+
+    // Email could have been changed after the token was sent
+    let updated = conn.execute(&Person::update_builder()
+                         .filter("ID = $2 and Email = $3")
+                         .set("EmailVerified")
+                         .build(),
+                     &[&true, &person_id, &email])
+        .unwrap();
+
+    if updated == 0 {
+        return Err(InvalidTokenError::from_str("Email was changed since verification was \
+                                                requested")
+            .into());
+    }
+
+    Ok(Response::with(StatusCode::Ok))
+}
+
+// Issues a fresh verification token for given email and mails it
+pub fn send_email_verification(conn: &Connection, person_id: i32, email: String) {
+    let now = chrono::UTC::now().naive_local();
+
+    // Only the latest verification token stays valid
+    conn.execute(&EmailVerification::update_builder()
+                     .filter("PersonID = $2 and Used = false")
+                     .set("Used")
+                     .build(),
+                 &[&true, &person_id])
+        .unwrap();
+
+    let verification = EmailVerification {
+        ID: 0,
+        Token: new_token(),
+        PersonID: person_id,
+        Email: email,
+        Created: now,
+        Expires: now + chrono::Duration::seconds(*config::EMAIL_VERIFICATION_TTL),
+        Used: false,
+    };
+
+    conn.execute(&EmailVerification::insert_query(),
+                 &verification.insert_args())
+        .unwrap();
+
+    mailer::send(Mail {
+        to: verification.Email,
+        subject: "Email verification".to_owned(),
+        body: format!("Hello!\r\n\r\n\
+                       Use the following token to confirm your email address, \
+                       it expires at {} UTC:\r\n\r\n{}\r\n",
+                      verification.Expires.format("%Y-%m-%d %H:%M"),
+                      verification.Token),
+    });
+}
@@ -0,0 +1,296 @@
+use iron::prelude::*;
+use router::Router;
+use hyper::status::StatusCode;
+use params::{Params, FromValue};
+use postgres::types::ToSql;
+use postgres::{Connection, GenericConnection};
+use std::str::FromStr;
+use std::i32;
+
+use super::request_body;
+use ::api::authorization::{Authorizer, Authorized};
+use ::api::ruleset;
+use ::api::validation;
+use ::api::ruleset::DEFAULT_RULESET_ID;
+use ::proto::response::*;
+use ::proto::error::*;
+use ::proto::schema::*;
+use ::db::schema::*;
+use ::db::schemaext::*;
+use ::db::*;
+
+// Every admin endpoint is for signed in administrators only
+fn authorize_admin(conn: &Connection, req: &mut Request) -> ApiResult<Authorized> {
+    let user = Authorizer::authorize_request(conn, req)?;
+    user.require_session()?;
+    user.check_admin()?;
+
+    Ok(user)
+}
+
+pub fn get_persons(req: &mut Request) -> IronResult<Response> {
+    let ofst = req.get_ref::<Params>()
+        .unwrap()
+        .find(&["offset"])
+        .map(|val| i32::from_value(val).unwrap_or(0))
+        .unwrap_or(0);
+
+    let cnt = req.extensions
+        .get::<Router>()
+        .unwrap()
+        .find("cnt")
+        .map(|s| i32::from_str(s).unwrap())
+        .unwrap_or(i32::MAX);
+
+    let conn = get_db_connection();
+    let user = authorize_admin(&conn, req)?;
+
+    info!("request GET /admin/persons/{}?offset={} {{ id: {} }}", cnt, ofst, user.id);
+
+    let persons = conn.query(&Person::select_builder()
+                   .columns("ID,Login,Name,Email,EmailVerified")
+                   .order_by("ID")
+                   .limit(cnt)
+                   .offset(ofst)
+                   .build(),
+               &[])
+        .unwrap()
+        .into_iter()
+        .map(AccountInfo::from)
+        .collect::<Vec<AccountInfo>>();
+
+    Ok(persons.as_response())
+}
+
+pub fn update_person(req: &mut Request) -> IronResult<Response> {
+    let person_id = req.extensions
+        .get::<Router>()
+        .unwrap()
+        .find("id")
+        .map(|s| i32::from_str(s).unwrap())
+        .expect("No Person ID in request");
+
+    let update_person: AdminUpdatePerson = request_body(req)?;
+
+    let conn = get_db_connection();
+    let user = authorize_admin(&conn, req)?;
+
+    info!("request POST /admin/person/{} {{ id: {}, {:?} }}",
+          person_id,
+          user.id,
+          update_person);
+
+    if conn.query(&Person::select_builder()
+                   .filter("ID = $1")
+                   .build(),
+               &[&person_id])
+        .unwrap()
+        .is_empty() {
+        return Err(NotFoundError::from_str(format!("No person with id {}", person_id)).into());
+    }
+
+    validation::fields(vec![update_person.Name
+                                .as_ref()
+                                .and_then(|name| validation::name(name)),
+                            update_person.Email
+                                .as_ref()
+                                .and_then(|email| validation::email("Email", email))])?;
+
+    // Platform must not be left without administrators by accident
+    if person_id == user.id && update_person.Admin == Some(false) {
+        return Err(NotAuthorizedError::from_str("Administrator can't revoke own role").into());
+    }
+
+    let mut update = Person::update_builder().filter(format!("ID = {}", person_id));
+    let mut values: Vec<&ToSql> = Vec::with_capacity(3);
+
+    if let Some(name) = update_person.Name.as_ref() {
+        update = update.set("Name");
+        values.push(name);
+    }
+
+    if let Some(email) = update_person.Email.as_ref() {
+        update = update.set("Email");
+        values.push(email);
+    }
+
+    if let Some(verified) = update_person.EmailVerified.as_ref() {
+        update = update.set("EmailVerified");
+        values.push(verified);
+    }
+
+    // Hotel.OwnerPersonID references Owner, hotels have to change hands first
+    if update_person.Owner == Some(false) {
+        let owns_hotels = !conn.query(&Hotel::select_builder()
+                       .columns("ID")
+                       .filter("OwnerPersonID = $1")
+                       .build(),
+                   &[&person_id])
+            .unwrap()
+            .is_empty();
+
+        if owns_hotels {
+            return Err(NotAuthorizedError::from_str("Owned hotels must be transferred before \
+                                                     revoking owner role")
+                .into());
+        }
+    }
+
+    let trans = conn.transaction().unwrap();
+
+    if !values.is_empty() {
+        trans.execute(&update.build(), &values)
+            .map_err(validation::person_error)?;
+    }
+
+    if let Some(owner) = update_person.Owner {
+        set_membership(&trans, "Owner", person_id, owner);
+    }
+
+    if let Some(admin) = update_person.Admin {
+        set_membership(&trans, "Admin", person_id, admin);
+    }
+
+    trans.commit().unwrap();
+
+    Authorizer::invalidate_roles(person_id);
+
+    Ok(Response::with(StatusCode::Ok))
+}
+
+// Owner and Admin tables just list persons holding the role
+fn set_membership(conn: &GenericConnection, table: &str, person_id: i32, member: bool) {
+    let query = if member {
+        InsertQueryBuilder::with_template("INSERT INTO $table ($columns) VALUES ($placeholders) \
+                                           ON CONFLICT DO NOTHING;")
+            .table(table)
+            .set("PersonID")
+            .build()
+    } else {
+        DeleteQueryBuilder::default()
+            .from_tables(table)
+            .filter("PersonID = $1")
+            .build()
+    };
+
+    conn.execute(&query, &[&person_id]).unwrap();
+}
+
+pub fn set_hotel_owner(req: &mut Request) -> IronResult<Response> {
+    let hotel_id = req.extensions
+        .get::<Router>()
+        .unwrap()
+        .find("id")
+        .map(|s| i32::from_str(s).unwrap())
+        .expect("No Hotel ID in request");
+
+    let new_owner: HotelOwner = request_body(req)?;
+
+    let conn = get_db_connection();
+    let user = authorize_admin(&conn, req)?;
+
+    info!("request POST /admin/hotel/{}/owner {{ id: {}, {:?} }}",
+          hotel_id,
+          user.id,
+          new_owner);
+
+    let old_owner = conn.query(&Hotel::select_builder()
+                   .columns("OwnerPersonID")
+                   .filter("ID = $1")
+                   .build(),
+               &[&hotel_id])
+        .unwrap()
+        .into_iter()
+        .map(|row| row.get::<_, i32>("OwnerPersonID"))
+        .last()
+        .ok_or(box NotFoundError::from_str(format!("No Hotel with id {} found", hotel_id)) as
+               Box<ApiError>)?;
+
+    if conn.query(&Owner::select_builder()
+                   .filter("PersonID = $1")
+                   .build(),
+               &[&new_owner.OwnerPersonID])
+        .unwrap()
+        .is_empty() {
+        return Err(NotFoundError::from_str(format!("Person {} is not an owner",
+                                                   new_owner.OwnerPersonID))
+            .into());
+    }
+
+    conn.execute(&Hotel::update_builder()
+                     .set("OwnerPersonID")
+                     .filter("ID = $2")
+                     .build(),
+                 &[&new_owner.OwnerPersonID, &hotel_id])
+        .unwrap();
+
+    Authorizer::invalidate_roles(old_owner);
+    Authorizer::invalidate_roles(new_owner.OwnerPersonID);
+
+    Ok(Response::with(StatusCode::Ok))
+}
+
+pub fn get_default_ruleset(req: &mut Request) -> IronResult<Response> {
+    let conn = get_db_connection();
+    let user = authorize_admin(&conn, req)?;
+
+    info!("request GET /admin/ruleset/default {{ id: {} }}", user.id);
+
+    let ruleset = conn.query(&RuleSet::select_builder()
+                   .filter("ID = $1")
+                   .build(),
+               &[&*DEFAULT_RULESET_ID])
+        .unwrap()
+        .into_iter()
+        .map(RuleSet::from)
+        .last()
+        .expect("Default ruleset is missing");
+
+    Ok(ruleset.as_response())
+}
+
+pub fn update_default_ruleset(req: &mut Request) -> IronResult<Response> {
+    let update_ruleset: UpdateRuleSet = request_body(req)?;
+
+    let conn = get_db_connection();
+    let user = authorize_admin(&conn, req)?;
+
+    info!("request POST /admin/ruleset/default {{ id: {}, {:?} }}",
+          user.id,
+          update_ruleset);
+
+    let mut update = RuleSet::update_builder().filter(format!("ID = {}", *DEFAULT_RULESET_ID));
+    let mut values: Vec<&ToSql> = Vec::with_capacity(2);
+
+    if let Some(name) = update_ruleset.Name.as_ref() {
+        update = update.set("Name");
+        values.push(name);
+    }
+
+    // Broken default ruleset would break every hotel using it
+    let rules = match update_ruleset.Body.as_ref() {
+        Some(body) => {
+            update = update.set("Body");
+            values.push(body);
+            Some(ruleset::decode_rules(body)?)
+        }
+        None => None,
+    };
+
+    if values.is_empty() {
+        return Ok(Response::with(StatusCode::Ok));
+    }
+
+    let trans = conn.transaction().unwrap();
+
+    trans.execute(&update.build(), &values)
+        .unwrap();
+
+    if let Some(rules) = rules {
+        ruleset::reload_levels(&trans, *DEFAULT_RULESET_ID, rules);
+    }
+
+    trans.commit().unwrap();
+
+    Ok(Response::with(StatusCode::Ok))
+}
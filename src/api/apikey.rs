@@ -0,0 +1,237 @@
+use router::Router;
+use hyper::status::StatusCode;
+use iron::prelude::*;
+use postgres::Connection;
+use std::str::FromStr;
+use std::i32;
+use chrono;
+
+use super::request_body;
+use ::api::authorization::{Authorizer, new_token};
+use ::api::password::token_digest;
+use ::api::audit;
+use ::api::audit::{AuthEventKind, Origin};
+use ::proto::error::*;
+use ::proto::response::*;
+use ::proto::schema::*;
+use ::db::schema::*;
+use ::db::*;
+
+// Distinguishes keys from session tokens in the same headers
+const KEY_PREFIX: &'static str = "hmsk_";
+
+// Matches varchar(64) column of ApiKey
+const MAX_NAME_LEN: usize = 64;
+
+// Keys younger than this are not touched on every request
+const LAST_USED_RESOLUTION_SECS: i64 = 60;
+
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(KEY_PREFIX)
+}
+
+pub fn parse_scopes(scopes: &str) -> Vec<Scope> {
+    scopes.split(',')
+        .filter_map(|scope| Scope::from_str(scope).ok())
+        .collect()
+}
+
+fn join_scopes(scopes: &[Scope]) -> String {
+    scopes.iter()
+        .map(Scope::as_str)
+        .collect::<Vec<&str>>()
+        .join(",")
+}
+
+fn key_hotels(conn: &Connection, key_id: i32) -> Vec<i32> {
+    conn.query(&ApiKeyHotel::select_builder()
+                   .columns("HotelID")
+                   .filter("ApiKeyID = $1")
+                   .build(),
+               &[&key_id])
+        .unwrap()
+        .into_iter()
+        .map(|row| row.get::<_, i32>("HotelID"))
+        .collect()
+}
+
+// Looks up active key and hotels it is bound to, tracking its last use.
+// Only digest of the key is stored, so leaked database doesn't leak keys
+pub fn find(conn: &Connection, key: &str) -> Option<(ApiKey, Vec<i32>)> {
+    let api_key = conn.query(&ApiKey::select_builder()
+                   .filter("KeyHash = $1 and Revoked = false")
+                   .build(),
+               &[&token_digest(key)])
+        .unwrap()
+        .into_iter()
+        .map(ApiKey::from)
+        .last();
+
+    api_key.map(|api_key| {
+        let now = chrono::UTC::now().naive_local();
+        let stale = api_key.LastUsed
+            .map_or(true, |last_used| {
+                now - last_used > chrono::Duration::seconds(LAST_USED_RESOLUTION_SECS)
+            });
+
+        if stale {
+            conn.execute(&ApiKey::update_builder()
+                             .filter(format!("ID = {}", api_key.ID))
+                             .set("LastUsed")
+                             .build(),
+                         &[&now])
+                .unwrap();
+        }
+
+        let hotels = key_hotels(conn, api_key.ID);
+        (api_key, hotels)
+    })
+}
+
+pub fn put_api_key(req: &mut Request) -> IronResult<Response> {
+    let new_key: NewApiKey = request_body(req)?;
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_session()?;
+
+    info!("request PUT /apikey/ {{ id: {}, {:?} }}", user.id, new_key);
+
+    if new_key.HotelIDs.is_empty() || new_key.Scopes.is_empty() {
+        return Err(IncompleteDataError::from_str("API key must be bound to at least one hotel \
+                                                  and scope")
+            .into());
+    }
+
+    if new_key.Name.trim().is_empty() || new_key.Name.chars().count() > MAX_NAME_LEN {
+        let detail = ErrorDetail::new("Name",
+                                      &format!("Must be 1 to {} characters long", MAX_NAME_LEN));
+        return Err(InvalidFieldsError::with_details("Invalid API key", vec![detail]).into());
+    }
+
+    for hotel_id in &new_key.HotelIDs {
+        user.check_owner_of(*hotel_id)?;
+    }
+
+    // Repeated hotel would violate the binding's primary key
+    let mut hotel_ids = new_key.HotelIDs.clone();
+    hotel_ids.sort();
+    hotel_ids.dedup();
+
+    let mut scopes: Vec<Scope> = Vec::with_capacity(new_key.Scopes.len());
+    for scope in &new_key.Scopes {
+        if !scopes.contains(scope) {
+            scopes.push(*scope);
+        }
+    }
+
+    // Key is useless without its hotels, so both are stored or neither
+    let trans = conn.transaction().unwrap();
+
+    let key = format!("{}{}", KEY_PREFIX, new_token());
+    let id: i32 = trans.query(&InsertQueryBuilder::with_template("INSERT INTO $table ($columns) \
+                                                                  VALUES ($placeholders) \
+                                                                  RETURNING ID;")
+                        .table("ApiKey")
+                        .set("PersonID")
+                        .set("Name")
+                        .set("KeyHash")
+                        .set("Scopes")
+                        .set("Created")
+                        .set("LastUsed")
+                        .set("Revoked")
+                        .build(),
+                    &[&user.id,
+                      &new_key.Name,
+                      &token_digest(&key),
+                      &join_scopes(&scopes),
+                      &chrono::UTC::now().naive_local(),
+                      &None::<chrono::NaiveDateTime>,
+                      &false])
+        .unwrap()
+        .get(0)
+        .get("ID");
+
+    for hotel_id in &hotel_ids {
+        trans.execute(&ApiKeyHotel::insert_query(),
+                      &ApiKeyHotel {
+                              ApiKeyID: id,
+                              HotelID: *hotel_id,
+                          }
+                          .insert_args())
+            .unwrap();
+    }
+
+    trans.commit().unwrap();
+
+    Ok(CreatedApiKey {
+            ID: id,
+            Key: key,
+        }
+        .as_response())
+}
+
+pub fn get_api_keys(req: &mut Request) -> IronResult<Response> {
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_session()?;
+
+    info!("request GET /apikeys/ {{ id: {} }}", user.id);
+
+    let keys = conn.query(&ApiKey::select_builder()
+                   .filter("PersonID = $1")
+                   .order_by("Created DESC")
+                   .build(),
+               &[&user.id])
+        .unwrap()
+        .into_iter()
+        .map(ApiKey::from)
+        .map(|api_key| {
+            ApiKeyInfo {
+                ID: api_key.ID,
+                Name: api_key.Name,
+                HotelIDs: key_hotels(&conn, api_key.ID),
+                Scopes: parse_scopes(&api_key.Scopes),
+                Created: api_key.Created,
+                LastUsed: api_key.LastUsed,
+                Revoked: api_key.Revoked,
+            }
+        })
+        .collect::<Vec<ApiKeyInfo>>();
+
+    Ok(keys.as_response())
+}
+
+pub fn revoke_api_key(req: &mut Request) -> IronResult<Response> {
+    let key_id = req.extensions
+        .get::<Router>()
+        .unwrap()
+        .find("id")
+        .map(|s| i32::from_str(s).unwrap())
+        .expect("No API key ID in request");
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_session()?;
+
+    info!("request DELETE /apikey/{} {{ id: {} }}", key_id, user.id);
+
+    let revoked = conn.execute(&ApiKey::update_builder()
+                     .set("Revoked")
+                     .filter("ID = $2 and PersonID = $3")
+                     .build(),
+                 &[&true, &key_id, &user.id])
+        .unwrap();
+
+    if revoked == 0 {
+        return Err(NotFoundError::from_str(format!("No API key with id {}", key_id)).into());
+    }
+
+    audit::record(&*conn,
+                  AuthEventKind::ApiKeyRevoked,
+                  Some(user.id),
+                  None,
+                  &Origin::of(req));
+
+    Ok(Response::with(StatusCode::Ok))
+}
@@ -0,0 +1,177 @@
+use iron::prelude::*;
+use router::Router;
+use params::{Params, FromValue};
+use postgres::types::ToSql;
+use postgres::GenericConnection;
+use std::str::FromStr;
+use std::i32;
+use chrono;
+
+use super::datetime_param;
+use ::api::authorization::{Authorizer, user_agent};
+use ::proto::error::*;
+use ::proto::response::*;
+use ::db::schema::*;
+use ::db::*;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AuthEventKind {
+    Signup,
+    Signin,
+    SigninFailed,
+    SigninLocked,
+    SecondFactorFailed,
+    Signout,
+    SessionRevoked,
+    PasswordChanged,
+    PasswordReset,
+    ApiKeyRevoked,
+    AccountDeleted,
+}
+
+impl AuthEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            AuthEventKind::Signup => "Signup",
+            AuthEventKind::Signin => "Signin",
+            AuthEventKind::SigninFailed => "SigninFailed",
+            AuthEventKind::SigninLocked => "SigninLocked",
+            AuthEventKind::SecondFactorFailed => "SecondFactorFailed",
+            AuthEventKind::Signout => "Signout",
+            AuthEventKind::SessionRevoked => "SessionRevoked",
+            AuthEventKind::PasswordChanged => "PasswordChanged",
+            AuthEventKind::PasswordReset => "PasswordReset",
+            AuthEventKind::ApiKeyRevoked => "ApiKeyRevoked",
+            AuthEventKind::AccountDeleted => "AccountDeleted",
+        }
+    }
+}
+
+// Where a request came from, user agent is whatever client claims to be
+pub struct Origin {
+    pub address: String,
+    pub user_agent: Option<String>,
+}
+
+impl Origin {
+    pub fn of(req: &Request) -> Self {
+        Origin {
+            address: req.remote_addr.ip().to_string(),
+            user_agent: user_agent(req),
+        }
+    }
+}
+
+// Appends an event, table rejects updates and deletes so the trail can't be rewritten
+pub fn record(conn: &GenericConnection,
+              kind: AuthEventKind,
+              person_id: Option<i32>,
+              login: Option<&str>,
+              origin: &Origin) {
+    conn.execute(&AuthEvent::insert_query(),
+                 &[&person_id,
+                   &login,
+                   &kind.as_str(),
+                   &origin.address,
+                   &origin.user_agent,
+                   &chrono::UTC::now().naive_local()])
+        .unwrap();
+}
+
+// Deleted account keeps its events, but nothing that tells who was behind them
+pub fn scrub(conn: &GenericConnection, person_id: i32, login: &str) {
+    conn.execute(&AuthEvent::update_builder()
+                     .set("Login")
+                     .set("Address")
+                     .set("UserAgent")
+                     .filter("PersonID = $4 or Login = $5")
+                     .build(),
+                 &[&None::<String>, &None::<String>, &None::<String>, &person_id, &login])
+        .unwrap();
+}
+
+// Admins see every event, owners only their own and those of their hotels' staff
+pub fn get_auth_events(req: &mut Request) -> IronResult<Response> {
+    let ofst = req.get_ref::<Params>()
+        .unwrap()
+        .find(&["offset"])
+        .map(|val| i32::from_value(val).unwrap_or(0))
+        .unwrap_or(0);
+
+    let person_id = req.get_ref::<Params>()
+        .unwrap()
+        .find(&["person"])
+        .and_then(|val| i32::from_value(val));
+
+    let cnt = req.extensions
+        .get::<Router>()
+        .unwrap()
+        .find("cnt")
+        .map(|s| i32::from_str(s).unwrap())
+        .unwrap_or(i32::MAX);
+
+    let from = datetime_param(req, "from")?;
+    let to = datetime_param(req, "to")?;
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_session()?;
+
+    info!("request GET /audit/auth/{}?offset={} {{ id: {}, person: {:?}, from: {:?}, to: \
+           {:?} }}",
+          cnt,
+          ofst,
+          user.id,
+          person_id,
+          from,
+          to);
+
+    if !user.roles.Admin && !user.roles.Owner {
+        return Err(NotAuthorizedError::from_str("Only owners and administrators can view \
+                                                 authentication events")
+            .into());
+    }
+
+    let mut filters = Vec::new();
+    let mut values: Vec<&ToSql> = Vec::with_capacity(4);
+
+    if !user.roles.Admin {
+        values.push(&user.id);
+        filters.push(format!("(PersonID = ${0} or PersonID IN (SELECT EmployedIn.PersonID FROM \
+                              EmployedIn, Hotel WHERE EmployedIn.HotelID = Hotel.ID and \
+                              Hotel.OwnerPersonID = ${0}))",
+                             values.len()));
+    }
+
+    if let Some(ref person_id) = person_id {
+        values.push(person_id);
+        filters.push(format!("PersonID = ${}", values.len()));
+    }
+
+    if let Some(ref from) = from {
+        values.push(from);
+        filters.push(format!("Created >= ${}", values.len()));
+    }
+
+    if let Some(ref to) = to {
+        values.push(to);
+        filters.push(format!("Created < ${}", values.len()));
+    }
+
+    let mut query = AuthEvent::select_builder()
+        .order_by("Created DESC, ID DESC")
+        .limit(cnt)
+        .offset(ofst);
+
+    if !filters.is_empty() {
+        query = query.filter(filters.join(" and "));
+    }
+
+    let events = conn.query(&query.build(), &values)
+        .unwrap()
+        .into_iter()
+        .map(AuthEvent::from)
+        .collect::<Vec<AuthEvent>>();
+
+    Ok(events.as_response())
+}
@@ -1,30 +1,82 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
 use std::sync::RwLock;
 use std::sync::Arc;
 use std::borrow::Borrow;
+use std::hash::Hash;
 
-use hyper::header::CookiePair;
-use postgres::Connection;
+use chrono;
+use chrono::NaiveDateTime;
+use hyper::header::{CookiePair, UserAgent};
+use hyper::status::StatusCode;
+use postgres::{Connection, GenericConnection};
 use iron::prelude::*;
 use oven::prelude::*;
+use rand::{OsRng, Rng};
+use rustc_serialize::hex::ToHex;
 
 use super::request_body;
+use ::api::account;
+use ::api::apikey;
+use ::api::audit;
+use ::api::audit::{AuthEventKind, Origin};
+use ::api::lockout;
+use ::api::twofactor;
+use ::api::session::{SessionStore, PgSessionStore};
+use ::api::password;
+use ::api::password::Verification;
+use ::api::validation;
 use ::proto::schema::*;
 use ::proto::error::*;
 use ::proto::response::*;
 use ::db::schema::*;
 use ::db::*;
+use ::config;
 
 pub type Token = String;
 
+// Generates an opaque hex-encoded token from 32 bytes of OS randomness
+pub fn new_token() -> Token {
+    let mut bytes = [0u8; 32];
+    OsRng::new()
+        .expect("Failed to open OS random number generator")
+        .fill_bytes(&mut bytes);
+
+    bytes.to_hex()
+}
+
 pub fn signin(req: &mut Request) -> IronResult<Response> {
     let signin_data: SigninData = request_body(req)?;
 
     info!("request POST /signin {{ {:?} }}", signin_data);
 
-    let token = Authorizer::signin(&get_db_connection(), &signin_data)?;
+    let conn = get_db_connection();
+    match Authorizer::signin(&conn, &signin_data, &Origin::of(req))? {
+        SigninState::Authorized(token) => respond_with_token(&conn, token),
+        SigninState::Pending2FA(token) => {
+            Ok(SigninResponse {
+                    Token: token,
+                    Roles: None,
+                    Pending2FA: true,
+                }
+                .as_response())
+        }
+    }
+}
+
+// Second signin step for persons with two-factor authentication enabled
+pub fn signin_2fa(req: &mut Request) -> IronResult<Response> {
+    let signin_data: TwoFactorSignin = request_body(req)?;
+
+    info!("request POST /signin/2fa {{ {:?} }}", signin_data);
+
+    let conn = get_db_connection();
+    let token = Authorizer::confirm_second_factor(&conn,
+                                                  &signin_data.Token,
+                                                  &signin_data.Code,
+                                                  &Origin::of(req))?;
 
-    respond_with_roles_and_token(token)
+    respond_with_token(&conn, token)
 }
 
 pub fn signup(req: &mut Request) -> IronResult<Response> {
@@ -32,98 +84,418 @@ pub fn signup(req: &mut Request) -> IronResult<Response> {
 
     info!("request POST /signup {{ {:?} }}", signup_data);
 
-    let token = Authorizer::signup(&get_db_connection(), &signup_data)?;
+    let conn = get_db_connection();
+    let token = Authorizer::signup(&conn, &signup_data, &Origin::of(req))?;
 
-    respond_with_roles_and_token(token)
+    respond_with_token(&conn, token)
 }
 
-fn respond_with_roles_and_token(token: String) -> IronResult<Response> {
-    let id = Authorizer::get_id(&token)?;
-    let roles = Authorizer::get_roles(&get_db_connection(), id)?;
+pub fn signout(req: &mut Request) -> IronResult<Response> {
+    let token = Authorizer::request_token(req)?;
 
-    let mut response: Response = roles.as_response();
-    let mut cookie = CookiePair::new("token".to_string(), token.to_owned());
+    info!("request POST /signout");
+
+    Authorizer::signout(&get_db_connection(), &token, &Origin::of(req));
+
+    let mut response = Response::with(StatusCode::Ok);
+    let mut cookie = CookiePair::new("token".to_string(), String::new());
+    cookie.path = Some(String::new());
+    cookie.max_age = Some(0);
+
+    response.set_cookie(cookie);
+    Ok(response)
+}
+
+fn respond_with_token(conn: &Connection, token: String) -> IronResult<Response> {
+    let id = Authorizer::get_id(conn, &token)?;
+
+    // Fresh signin always resolves roles from the database
+    Authorizer::invalidate_roles(id);
+    let roles = Authorizer::get_roles(conn, id)?;
+
+    let mut response: Response = SigninResponse {
+            Token: token.clone(),
+            Roles: Some(roles),
+            Pending2FA: false,
+        }
+        .as_response();
+    let mut cookie = CookiePair::new("token".to_string(), token);
 
     // Nulling the path to tell browser to pass cookie for whole domain
     cookie.path = Some(String::new());
+    cookie.max_age = Some(*config::TOKEN_TTL as u64);
 
     response.set_cookie(cookie);
     Ok(response)
 }
 
+fn header_token(req: &Request) -> Option<Token> {
+    let header = |name: &str| {
+        req.headers
+            .get_raw(name)
+            .and_then(|values| values.first())
+            .and_then(|value| String::from_utf8(value.clone()).ok())
+    };
+
+    if let Some(authorization) = header("Authorization") {
+        let mut parts = authorization.trim().splitn(2, ' ');
+        let scheme = parts.next().unwrap_or("");
+
+        if scheme.eq_ignore_ascii_case("Bearer") {
+            return parts.next().map(|token| token.trim().to_owned());
+        }
+    }
+
+    header("token").map(|token| token.trim().to_owned())
+}
+
+pub fn user_agent(req: &Request) -> Option<String> {
+    req.headers
+        .get::<UserAgent>()
+        .map(|agent| agent.0.chars().take(255).collect())
+}
+
+pub enum SigninState {
+    Authorized(Token),
+    // Password accepted, token has to be confirmed with a second factor
+    Pending2FA(Token),
+}
+
 pub struct Authorized {
     pub id: i32,
     pub roles: Roles,
+    // Set when request is made with an API key instead of a session
+    pub api_key: Option<KeyGrant>,
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyGrant {
+    pub id: i32,
+    pub scopes: Vec<Scope>,
+}
+
+impl Authorized {
+    pub fn require_scope(&self, scope: Scope) -> ApiResult<()> {
+        match self.api_key {
+            Some(ref key) if !key.scopes.contains(&scope) => {
+                Err(box NotAuthorizedError::from_str(format!("API key lacks {} scope",
+                                                             scope.as_str())))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Credentials, sessions and keys are managed only by a signed in person
+    pub fn require_session(&self) -> ApiResult<()> {
+        if self.api_key.is_some() {
+            Err(box NotAuthorizedError::from_str("Not allowed with API key"))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn owns(&self, hotel_id: i32) -> bool {
+        self.roles.Admin || self.roles.Owns.as_ref().map_or(false, |owns| owns.contains(&hotel_id))
+    }
+
+    pub fn check_admin(&self) -> ApiResult<()> {
+        if self.roles.Admin {
+            Ok(())
+        } else {
+            Err(box NotAuthorizedError::from_str("Only administrator can do this"))
+        }
+    }
+
+    pub fn employed_in(&self, hotel_id: i32) -> bool {
+        self.roles.HotelRoles.contains_key(&hotel_id)
+    }
+
+    pub fn role_in(&self, hotel_id: i32) -> Option<StaffRole> {
+        self.roles.HotelRoles.get(&hotel_id).cloned()
+    }
+
+    pub fn check_owner_of(&self, hotel_id: i32) -> ApiResult<()> {
+        if self.owns(hotel_id) {
+            Ok(())
+        } else {
+            Err(box NotAuthorizedError::from_str(format!("Not owner of hotel {}", hotel_id)))
+        }
+    }
+
+    pub fn check_staff_of(&self, hotel_id: i32) -> ApiResult<()> {
+        if self.owns(hotel_id) || self.employed_in(hotel_id) {
+            Ok(())
+        } else {
+            Err(box NotAuthorizedError::from_str(format!("Not owner or employee of hotel {}",
+                                                         hotel_id)))
+        }
+    }
+
+    // Owner of the hotel passes any role check
+    pub fn check_role_in(&self, hotel_id: i32, allowed: &[StaffRole]) -> ApiResult<()> {
+        if self.owns(hotel_id) || self.role_in(hotel_id).map_or(false, |r| allowed.contains(&r)) {
+            Ok(())
+        } else {
+            Err(box NotAuthorizedError::from_str(format!("Not owner of hotel {} or one of {:?} \
+                                                          there",
+                                                         hotel_id,
+                                                         allowed)))
+        }
+    }
 }
 
 pub struct Authorizer;
 impl Authorizer {
-    pub fn signin(conn: &Connection, signin_data: &SigninData) -> ApiResult<Token> {
+    pub fn signin(conn: &Connection,
+                  signin_data: &SigninData,
+                  origin: &Origin)
+                  -> ApiResult<SigninState> {
+        let address = &origin.address;
+        let login = Some(signin_data.Login.as_str());
+
+        // Locked out attempts are rejected before the password is even looked at
+        if let Err(err) = lockout::check(conn, &signin_data.Login, address) {
+            audit::record(conn, AuthEventKind::SigninLocked, None, login, origin);
+            return Err(err);
+        }
+
         let query = Person::select_builder()
-            .filter("Login = $1 and PassHash = $2")
+            .filter("Login = $1")
             .build();
 
-        let rows = conn.query(&query, &[&signin_data.Login, &signin_data.PassHash]).unwrap();
+        let rows = conn.query(&query, &[&signin_data.Login]).unwrap();
 
         assert!(rows.len() <= 1, "Database is inconsistent");
         if rows.is_empty() {
+            password::verify_dummy(&signin_data.Password);
+            lockout::record_failure(conn, &signin_data.Login, address);
+            audit::record(conn, AuthEventKind::SigninFailed, None, login, origin);
             return Err(box SigninError::from_str("Login-password pair not found"));
         }
 
         let person = Person::from(rows.get(0));
-        let token = person.ID.to_string();
-        TOKEN_MAP.put(token.clone(), person.ID);
+        match password::verify(&signin_data.Password, &person.PassHash) {
+            Verification::Valid => (),
+            Verification::NeedsUpgrade => Self::set_password(conn, person.ID, &signin_data.Password),
+            Verification::Invalid => {
+                lockout::record_failure(conn, &signin_data.Login, address);
+                audit::record(conn, AuthEventKind::SigninFailed, Some(person.ID), login, origin);
+                return Err(box SigninError::from_str("Login-password pair not found"));
+            }
+        }
+
+        // Failures are forgiven only once the second factor is passed too
+        if twofactor::enabled(conn, person.ID) {
+            let session = PgSessionStore::new(conn)
+                .create(person.ID, origin.user_agent.clone(), true);
+            return Ok(SigninState::Pending2FA(session.Token));
+        }
+
+        lockout::reset(conn, &signin_data.Login);
+        audit::record(conn, AuthEventKind::Signin, Some(person.ID), login, origin);
+
+        let session = PgSessionStore::new(conn).create(person.ID, origin.user_agent.clone(), false);
+
+        Ok(SigninState::Authorized(session.Token))
+    }
+
+    pub fn confirm_second_factor(conn: &Connection,
+                                 token: &str,
+                                 code: &str,
+                                 origin: &Origin)
+                                 -> ApiResult<Token> {
+        let store = PgSessionStore::new(conn);
+        let session = match store.find_pending(token) {
+            Some(session) => session,
+            None => return Err(box InvalidTokenError::from_str("Invalid or expired signin token")),
+        };
+
+        let login: String = conn.query(&Person::select_builder()
+                           .columns("Login")
+                           .filter("ID = $1")
+                           .build(),
+                       &[&session.PersonID])
+            .unwrap()
+            .get(0)
+            .get("Login");
+
+        let person_id = Some(session.PersonID);
+
+        // Codes are short, so they are throttled just like passwords
+        if let Err(err) = lockout::check(conn, &login, &origin.address) {
+            audit::record(conn, AuthEventKind::SigninLocked, person_id, Some(&login), origin);
+            return Err(err);
+        }
+
+        if !twofactor::verify(conn, session.PersonID, code) {
+            lockout::record_failure(conn, &login, &origin.address);
+            audit::record(conn,
+                          AuthEventKind::SecondFactorFailed,
+                          person_id,
+                          Some(&login),
+                          origin);
+            return Err(box SigninError::from_str("Invalid two-factor code"));
+        }
+
+        lockout::reset(conn, &login);
+        audit::record(conn, AuthEventKind::Signin, person_id, Some(&login), origin);
+
+        Ok(store.confirm(&session).Token)
+    }
+
+    pub fn signup(conn: &Connection,
+                  signup_data: &SignupData,
+                  origin: &Origin)
+                  -> ApiResult<Token> {
+        validation::signup(signup_data)?;
+
+        conn.execute(&Person::insert_query(),
+                     &[&signup_data.Login,
+                       &signup_data.Name,
+                       &signup_data.Email,
+                       &password::hash(&signup_data.Password),
+                       &false])
+            .map_err(validation::person_error)?;
+
+        let id: i32 = conn.query(&Person::select_builder()
+                           .columns("ID")
+                           .filter("Login = $1")
+                           .build(),
+                       &[&signup_data.Login])
+            .unwrap()
+            .get(0)
+            .get("ID");
+
+        account::send_email_verification(conn, id, signup_data.Email.clone());
+        audit::record(conn, AuthEventKind::Signup, Some(id), Some(&signup_data.Login), origin);
+
+        let session = PgSessionStore::new(conn).create(id, origin.user_agent.clone(), false);
+
+        Ok(session.Token)
+    }
 
-        Ok(token)
+    // Checks password of already authorized person, e.g. before changing credentials
+    pub fn check_password(conn: &Connection, id: i32, password: &str) -> bool {
+        let stored: String = conn.query(&Person::select_builder()
+                           .columns("PassHash")
+                           .filter("ID = $1")
+                           .build(),
+                       &[&id])
+            .unwrap()
+            .get(0)
+            .get("PassHash");
+
+        password::verify(password, &stored) != Verification::Invalid
     }
 
-    pub fn signup(conn: &Connection, signup_data: &SignupData) -> ApiResult<Token> {
-        match conn.execute(&Person::insert_query(),
-                           &[&signup_data.Login,
-                             &signup_data.Name,
-                             &signup_data.Email,
-                             &signup_data.PassHash]) {
-            Ok(_) => (),
-            Err(e) => return Err(box SignupError::from_str(format!("{}", e))),
+    pub fn set_password(conn: &GenericConnection, id: i32, new_password: &str) {
+        conn.execute(&Person::update_builder()
+                         .filter(format!("ID = {}", id))
+                         .set("PassHash")
+                         .build(),
+                     &[&password::hash(new_password)])
+            .unwrap();
+    }
+
+    pub fn signout(conn: &Connection, token: &str, origin: &Origin) {
+        let store = PgSessionStore::new(conn);
+
+        if let Some(session) = store.find(token) {
+            audit::record(conn, AuthEventKind::Signout, Some(session.PersonID), None, origin);
         }
 
-        Self::signin(conn, &SigninData {
-            Login: signup_data.Login.clone(),
-            PassHash: signup_data.PassHash.clone(),
-        })
+        store.revoke(token);
     }
 
     pub fn authorize_request(conn: &Connection, req: &mut Request) -> ApiResult<Authorized> {
-        let token_cookie = match req.get_cookie("token") {
-            Some(tc) => tc,
-            None => return Err(box NotAuthorizedError::from_str("No token found in request")),
-        };
+        let token = Self::request_token(req)?;
+
+        if apikey::is_api_key(&token) {
+            return Self::authorize_api_key(conn, &token);
+        }
 
-        let id = Self::get_id(&token_cookie.value)?;
+        let id = Self::get_id(conn, &token)?;
         Ok(Authorized {
             id: id,
             roles: Self::get_roles(conn, id)?,
+            api_key: None,
         })
     }
 
-    fn get_id(token: &str) -> ApiResult<i32> {
-        TOKEN_MAP.get(token)
-            .ok_or(box NotAuthorizedError::from_str("Token has expired"))
+    // Key acts on behalf of its creator, but only within hotels it is bound to
+    fn authorize_api_key(conn: &Connection, key: &str) -> ApiResult<Authorized> {
+        let (api_key, hotels) = apikey::find(conn, key)
+            .ok_or(box NotAuthorizedError::from_str("Invalid or revoked API key"))?;
+
+        let roles = Self::get_roles(conn, api_key.PersonID)?;
+        let scopes = apikey::parse_scopes(&api_key.Scopes);
+
+        Ok(Authorized {
+            id: api_key.PersonID,
+            roles: restrict_roles(roles, &hotels),
+            api_key: Some(KeyGrant {
+                id: api_key.ID,
+                scopes: scopes,
+            }),
+        })
+    }
+
+    // Token is taken from "Authorization: Bearer" header, "token" header or cookie
+    pub fn request_token(req: &mut Request) -> ApiResult<Token> {
+        if let Some(token) = header_token(req) {
+            return Ok(token);
+        }
+
+        req.get_cookie("token")
+            .map(|cookie| cookie.value.clone())
+            .ok_or(box NotAuthorizedError::from_str("No token found in request"))
+    }
+
+    fn get_id(conn: &Connection, token: &str) -> ApiResult<i32> {
+        PgSessionStore::new(conn)
+            .find(token)
+            .map(|session| session.PersonID)
+            .ok_or(box NotAuthorizedError::from_str("Invalid or expired token"))
     }
 
     pub fn get_roles(conn: &Connection, id: i32) -> ApiResult<Roles> {
-        macro_rules! query_all_with_id {
-            ($conn:ident, $table:ident) => (
-                $conn.query(&$table::select_builder()
-                                    .filter("PersonID = $1")
-                                    .build(), &[&id]).unwrap()
-            )
+        let now = chrono::UTC::now().naive_local();
+
+        if let Some(cached) = ROLES_MAP.get(&id) {
+            if cached.expires > now {
+                return Ok(cached.roles);
+            }
         }
 
-        let owner = !query_all_with_id!(conn, Owner).is_empty();
-        let manager = !query_all_with_id!(conn, Manager).is_empty();
-        let cleaner = !query_all_with_id!(conn, Cleaner).is_empty();
-        let receptionist = !query_all_with_id!(conn, Receptionist).is_empty();
+        let roles = Self::resolve_roles(conn, id)?;
+        ROLES_MAP.put(id,
+                      CachedRoles {
+                          roles: roles.clone(),
+                          expires: now + chrono::Duration::seconds(*config::ROLES_CACHE_TTL),
+                      });
+
+        Ok(roles)
+    }
+
+    // Must be called whenever ownership or employment of the person changes
+    pub fn invalidate_roles(id: i32) {
+        ROLES_MAP.remove(&id);
+    }
+
+    fn resolve_roles(conn: &Connection, id: i32) -> ApiResult<Roles> {
+        let admin = !conn.query(&Admin::select_builder()
+                           .filter("PersonID = $1")
+                           .build(),
+                       &[&id])
+            .unwrap()
+            .is_empty();
+
+        let owner = !conn.query(&Owner::select_builder()
+                           .filter("PersonID = $1")
+                           .build(),
+                       &[&id])
+            .unwrap()
+            .is_empty();
 
         let owned_hotels = if owner {
             Some(conn.query(&Hotel::select_builder()
@@ -139,28 +511,34 @@ impl Authorizer {
             None
         };
 
-        let employed_in = if manager || cleaner || receptionist {
-            Some(conn.query(&EmployedIn::select_builder()
-                           .columns("HotelID")
-                           .filter("PersonID = $1")
-                           .build(),
-                       &[&id])
-                .unwrap()
-                .into_iter()
-                .map(|row| row.get::<_, i32>("HotelID"))
-                .collect())
-        } else {
-            None
-        };
+        let hotel_roles = conn.query(&EmployedIn::select_builder()
+                       .filter("PersonID = $1")
+                       .build(),
+                   &[&id])
+            .unwrap()
+            .into_iter()
+            .map(EmployedIn::from)
+            .map(|emp| {
+                (emp.HotelID, StaffRole::from_str(&emp.Role).expect("Database is inconsistent"))
+            })
+            .collect::<BTreeMap<i32, StaffRole>>();
+
+        let has_role = |role| hotel_roles.values().any(|r| *r == role);
 
         let roles = Roles {
             ID: id,
+            Admin: admin,
             Owner: owner,
             Owns: owned_hotels,
-            Manager: manager,
-            Cleaner: cleaner,
-            Receptionist: receptionist,
-            EmployedIn: employed_in,
+            Manager: has_role(StaffRole::Manager),
+            Cleaner: has_role(StaffRole::Cleaner),
+            Receptionist: has_role(StaffRole::Receptionist),
+            EmployedIn: if hotel_roles.is_empty() {
+                None
+            } else {
+                Some(hotel_roles.keys().cloned().collect())
+            },
+            HotelRoles: hotel_roles.clone(),
         };
 
         debug!("{:?}", roles);
@@ -169,13 +547,44 @@ impl Authorizer {
     }
 }
 
-// Token storage
+fn restrict_roles(mut roles: Roles, hotels: &[i32]) -> Roles {
+    roles.Admin = false;
+    roles.Owns = roles.Owns.map(|owns| owns.into_iter().filter(|id| hotels.contains(id)).collect());
+
+    let hotel_roles = roles.HotelRoles
+        .into_iter()
+        .filter(|&(hotel_id, _)| hotels.contains(&hotel_id))
+        .collect::<BTreeMap<i32, StaffRole>>();
+
+    {
+        let has_role = |role| hotel_roles.values().any(|r| *r == role);
+        roles.Manager = has_role(StaffRole::Manager);
+        roles.Cleaner = has_role(StaffRole::Cleaner);
+        roles.Receptionist = has_role(StaffRole::Receptionist);
+    }
+
+    roles.EmployedIn = if hotel_roles.is_empty() {
+        None
+    } else {
+        Some(hotel_roles.keys().cloned().collect())
+    };
+    roles.HotelRoles = hotel_roles;
+
+    roles
+}
+
+// Roles cache, entries expire after ROLES_CACHE_TTL to bound staleness
+// of changes made by other instances
 lazy_static! {
-    static ref TOKEN_MAP: SyncMap<Token, i32> = SyncMap::new();
-   // static ref ROLES_MAP: SyncMap<i32, Roles> = SyncMap::new();
+    static ref ROLES_MAP: SyncMap<i32, CachedRoles> = SyncMap::new();
+}
+
+#[derive(Debug, Clone)]
+struct CachedRoles {
+    roles: Roles,
+    expires: NaiveDateTime,
 }
 
-use std::hash::Hash;
 struct SyncMap<K: Eq + Hash, V> {
     map: Arc<RwLock<HashMap<K, V>>>,
 }
@@ -188,14 +597,21 @@ impl<K, V> SyncMap<K, V>
         SyncMap { map: Arc::new(RwLock::new(HashMap::new())) }
     }
 
-    pub fn get<Q: ?Sized>(&self, token: &Q) -> Option<V>
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<V>
         where K: Borrow<Q>,
               Q: Hash + Eq
     {
-        self.map.read().unwrap().get(token.borrow()).cloned()
+        self.map.read().unwrap().get(key.borrow()).cloned()
     }
 
-    pub fn put(&self, token: K, id: V) {
-        self.map.write().unwrap().insert(token, id);
+    pub fn put(&self, key: K, value: V) {
+        self.map.write().unwrap().insert(key, value);
     }
-}
\ No newline at end of file
+
+    pub fn remove<Q: ?Sized>(&self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: Hash + Eq
+    {
+        self.map.write().unwrap().remove(key.borrow())
+    }
+}
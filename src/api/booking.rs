@@ -1,10 +1,18 @@
 use chrono;
+use chrono::NaiveDateTime;
 use iron::prelude::*;
 use router::Router;
+use params::{Params, FromValue};
 use hyper::status::StatusCode;
+use std::str::FromStr;
+use std::i32;
+use postgres::Connection;
+use postgres::types::ToSql;
+use postgres::error::{Error, SqlState};
 
-use super::request_body;
+use super::{request_body, datetime_param};
 use ::api::authorization::*;
+use ::api::ruleset;
 use ::proto::schema::*;
 use ::proto::response::*;
 use ::proto::error::*;
@@ -12,58 +20,530 @@ use ::db::schema::*;
 use ::db::schemaext::*;
 use ::db::*;
 
+// Receptionist may maintain the same booking many times, last time is kept
+pub const MAINTAINED_BY_UPSERT: &'static str = "INSERT INTO $table ($columns) VALUES \
+                                                ($placeholders) ON CONFLICT (BookingID, \
+                                                ReceptionistPersonID) DO UPDATE SET \
+                                                MaintainedAt = excluded.MaintainedAt;";
+
 pub fn get_booking_by_id(req: &mut Request) -> IronResult<Response> {
     let conn = get_db_connection();
     let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::ReadBookings)?;
 
     let booking_id = req.extensions
         .get::<Router>()
         .unwrap()
         .find("id")
-        .unwrap();
+        .map(|s| i32::from_str(s).unwrap())
+        .expect("No Booking ID in request");
 
     info!("request GET /api/booking/{} {{ id: {} }}",
           booking_id,
           user.id);
 
-    let booking = match conn.query(&Booking::select_builder()
-                   .filter("ID = $1")
-                   .build(),
-               &[&booking_id])
+    let booking = find_booking(&conn, booking_id)?;
+
+    if user.id != booking.ClientPersonID && user.check_staff_of(booking.HotelID).is_err() {
+        return Err(NotAuthorizedError::from_str("Access denied, nor booking's owner nor hotel \
+                                                 employee")
+            .into());
+    }
+
+    Ok(BookingDetails {
+            Booking: booking,
+            Nights: booking_nights(&conn, booking_id),
+        }
+        .as_response())
+}
+
+// Without hotel filter lists bookings of every hotel the user owns or works in
+pub fn get_bookings(req: &mut Request) -> IronResult<Response> {
+    let ofst = req.get_ref::<Params>()
+        .unwrap()
+        .find(&["offset"])
+        .map(|val| i32::from_value(val).unwrap_or(0))
+        .unwrap_or(0);
+
+    let cnt = req.extensions
+        .get::<Router>()
+        .unwrap()
+        .find("cnt")
+        .map(|s| i32::from_str(s).unwrap())
+        .unwrap_or(i32::MAX);
+
+    let int_param = |req: &mut Request, name: &str| {
+        req.get_ref::<Params>()
+            .unwrap()
+            .find(&[name])
+            .and_then(|val| i32::from_value(val))
+    };
+
+    let hotel_id = int_param(req, "hotel");
+    let client_id = int_param(req, "client");
+    let room_number = int_param(req, "room");
+    let reservation_id = int_param(req, "reservation");
+
+    let str_param = |req: &mut Request, name: &str| {
+        req.get_ref::<Params>()
+            .unwrap()
+            .find(&[name])
+            .and_then(|val| String::from_value(val))
+    };
+
+    let status = str_param(req, "status");
+    let sort = str_param(req, "sort");
+
+    let date_from = datetime_param(req, "date_from")?;
+    let date_to = datetime_param(req, "date_to")?;
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::ReadBookings)?;
+
+    info!("request GET /api/bookings/{}?offset={} {{ id: {}, hotel: {:?}, client: {:?}, room: \
+           {:?}, reservation: {:?}, status: {:?}, from: {:?}, to: {:?}, sort: {:?} }}",
+          cnt,
+          ofst,
+          user.id,
+          hotel_id,
+          client_id,
+          room_number,
+          reservation_id,
+          status,
+          date_from,
+          date_to,
+          sort);
+
+    let order = match sort.as_ref().map(String::as_str) {
+        None => "ID DESC",
+        Some("arrival") => "ArrivalTime, ID",
+        Some("-arrival") => "ArrivalTime DESC, ID DESC",
+        Some(_) => {
+            let detail = ErrorDetail::new("sort", "Must be one of arrival, -arrival");
+            return Err(InvalidFieldsError::with_details("Invalid sort order", vec![detail])
+                .into());
+        }
+    };
+
+    // Active is anything but cancelled
+    let (cancelled, status) = match status.as_ref().map(String::as_str) {
+        None => (None, None),
+        Some("active") => (Some(false), None),
+        Some(status) => {
+            match BookingStatus::from_str(status) {
+                Ok(status) => (None, Some(status.as_str())),
+                Err(_) => {
+                    let detail = ErrorDetail::new("status",
+                                                  "Must be active or one of Reserved, Confirmed, \
+                                                   CheckedIn, CheckedOut, NoShow, Cancelled");
+                    return Err(InvalidFieldsError::with_details("Invalid booking status",
+                                                                vec![detail])
+                        .into());
+                }
+            }
+        }
+    };
+
+    let mut filters = Vec::new();
+    let mut values: Vec<&ToSql> = Vec::with_capacity(8);
+
+    if let Some(ref hotel_id) = hotel_id {
+        user.check_staff_of(*hotel_id)?;
+        values.push(hotel_id);
+        filters.push(format!("HotelID = ${}", values.len()));
+    } else if !user.roles.Admin {
+        // Roles of API key are already narrowed to its hotels
+        let mut hotels = user.roles.Owns.clone().unwrap_or(Vec::new());
+        hotels.extend(user.roles.HotelRoles.keys().cloned());
+        hotels.sort();
+        hotels.dedup();
+
+        if hotels.is_empty() {
+            return Ok(Vec::<Booking>::new().as_response());
+        }
+
+        filters.push(format!("HotelID IN ({})",
+                             hotels.iter()
+                                 .map(|id| id.to_string())
+                                 .collect::<Vec<String>>()
+                                 .join(",")));
+    }
+
+    if let Some(ref client_id) = client_id {
+        values.push(client_id);
+        filters.push(format!("ClientPersonID = ${}", values.len()));
+    }
+
+    if let Some(ref room_number) = room_number {
+        values.push(room_number);
+        filters.push(format!("RoomNumber = ${}", values.len()));
+    }
+
+    if let Some(ref reservation_id) = reservation_id {
+        values.push(reservation_id);
+        filters.push(format!("ReservationID = ${}", values.len()));
+    }
+
+    if let Some(ref cancelled) = cancelled {
+        values.push(cancelled);
+        filters.push(format!("Cancelled = ${}", values.len()));
+    }
+
+    if let Some(ref status) = status {
+        values.push(status);
+        filters.push(format!("Status = ${}", values.len()));
+    }
+
+    // Stays overlapping the window, not only those starting in it
+    if let Some(ref date_from) = date_from {
+        values.push(date_from);
+        filters.push(format!("DepartureTime > ${}", values.len()));
+    }
+
+    if let Some(ref date_to) = date_to {
+        values.push(date_to);
+        filters.push(format!("ArrivalTime < ${}", values.len()));
+    }
+
+    let mut query = Booking::select_builder()
+        .order_by(order)
+        .limit(cnt)
+        .offset(ofst);
+
+    if !filters.is_empty() {
+        query = query.filter(filters.join(" and "));
+    }
+
+    let bookings = conn.query(&query.build(), &values)
         .unwrap()
         .into_iter()
         .map(Booking::from)
-        .last() {
-        Some(booking) => booking,
+        .collect::<Vec<Booking>>();
+
+    Ok(bookings.as_response())
+}
+
+pub fn cancel_booking(req: &mut Request) -> IronResult<Response> {
+    let booking_id = req.extensions
+        .get::<Router>()
+        .unwrap()
+        .find("id")
+        .map(|s| i32::from_str(s).unwrap())
+        .expect("No Booking ID in request");
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::WriteBookings)?;
+
+    info!("request POST /api/booking/{}/cancel {{ id: {} }}",
+          booking_id,
+          user.id);
+
+    let booking = find_booking(&conn, booking_id)?;
+    let now = chrono::UTC::now().naive_local();
+    check_changeable(&user, &booking, now)?;
+
+    let status = booking_status(&booking);
+    if !status.can_become(BookingStatus::Cancelled) {
+        return Err(ConflictError::from_str(format!("Booking {} is {}, it can't be cancelled",
+                                                   booking_id,
+                                                   status.as_str()))
+            .into());
+    }
+
+    let fee = ruleset::hotel_rules(&conn, booking.HotelID)?
+        .Cancellation
+        .map_or(0, |policy| policy.fee(booking.FullCost, booking.ArrivalTime, now));
+
+    // Concurrent cancellation must not charge the fee twice
+    let cancelled = conn.execute(&Booking::update_builder()
+                     .set("Cancelled")
+                     .set("Status")
+                     .set("CancelledBy")
+                     .set("CancelledAt")
+                     .set("CancellationFee")
+                     .filter("ID = $6 and Status = $7")
+                     .build(),
+                 &[&true,
+                   &BookingStatus::Cancelled.as_str(),
+                   &user.id,
+                   &now,
+                   &fee,
+                   &booking_id,
+                   &status.as_str()])
+        .unwrap();
+
+    if cancelled == 0 {
+        return Err(ConflictError::from_str(format!("Booking {} was changed meanwhile", booking_id))
+            .into());
+    }
+
+    Ok(find_booking(&conn, booking_id)?.as_response())
+}
+
+// Changes the stay, price is recalculated by the database on update
+pub fn update_booking(req: &mut Request) -> IronResult<Response> {
+    let booking_id = req.extensions
+        .get::<Router>()
+        .unwrap()
+        .find("id")
+        .map(|s| i32::from_str(s).unwrap())
+        .expect("No Booking ID in request");
+
+    let update_booking: UpdateBooking = request_body(req)?;
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::WriteBookings)?;
+
+    info!("request POST /api/booking/{} {{ id: {}, {:?} }}",
+          booking_id,
+          user.id,
+          update_booking);
+
+    let booking = find_booking(&conn, booking_id)?;
+    let now = chrono::UTC::now().naive_local();
+    check_changeable(&user, &booking, now)?;
+
+    let room_number = update_booking.RoomNumber.unwrap_or(booking.RoomNumber);
+    let arrival = update_booking.ArrivalTime.unwrap_or(booking.ArrivalTime);
+    let departure = update_booking.DepartureTime.unwrap_or(booking.DepartureTime);
+
+    validate_stay(&conn, booking.HotelID, room_number, arrival, departure)?;
+
+    let trans = conn.transaction().unwrap();
+
+    let new_cost = match trans.query(&UpdateQueryBuilder::with_template("UPDATE $table SET \
+                                                                        $columns $where_clause \
+                                                                        RETURNING FullCost;")
+                     .table("Booking")
+                     .set("RoomNumber")
+                     .set("ArrivalTime")
+                     .set("DepartureTime")
+                     .filter("ID = $4 and Cancelled = false")
+                     .build(),
+                 &[&room_number, &arrival, &departure, &booking_id]) {
+        Ok(rows) => rows.iter().map(|row| row.get::<_, i32>("FullCost")).last(),
+        Err(Error::Db(ref db_err)) if db_err.code == SqlState::ExclusionViolation => {
+            trans.finish().unwrap();
+            return Err(conflict_error(&conn,
+                                      booking.HotelID,
+                                      room_number,
+                                      arrival,
+                                      departure,
+                                      Some(booking_id))
+                .into());
+        }
+        Err(err) => panic!("{:?}", err),
+    };
+
+    let new_cost = match new_cost {
+        Some(new_cost) => new_cost,
         None => {
-            return Err(NotFoundError::from_str(format!("No booking with id {}", booking_id)).into())
+            return Err(ConflictError::from_str(format!("Booking {} was cancelled meanwhile",
+                                                       booking_id))
+                .into())
         }
     };
 
-    let employee = conn.query(&EmployedIn::select_builder()
-                   .filter("PersonID = $1")
-                   .build(),
-               &[&user.id])
+    let change = BookingChange {
+        ID: 0,
+        BookingID: booking_id,
+        ChangedBy: user.id,
+        ChangedAt: now,
+        OldRoomNumber: booking.RoomNumber,
+        OldArrivalTime: booking.ArrivalTime,
+        OldDepartureTime: booking.DepartureTime,
+        OldFullCost: booking.FullCost,
+        NewRoomNumber: room_number,
+        NewArrivalTime: arrival,
+        NewDepartureTime: departure,
+        NewFullCost: new_cost,
+        PriceDifference: new_cost - booking.FullCost,
+    };
+
+    trans.execute(&BookingChange::insert_query(), &change.insert_args())
+        .unwrap();
+
+    if user.role_in(booking.HotelID) == Some(StaffRole::Receptionist) {
+        trans.execute(&InsertQueryBuilder::with_template(MAINTAINED_BY_UPSERT)
+                          .table("MaintainedBy")
+                          .set("BookingID")
+                          .set("ReceptionistPersonID")
+                          .set("MaintainedAt")
+                          .build(),
+                      &[&booking_id, &user.id, &now])
+            .unwrap();
+    }
+
+    trans.commit().unwrap();
+
+    Ok(change.as_response())
+}
+
+// Client can change own booking, receptionists, managers and owner any in their hotel
+fn check_changeable(user: &Authorized, booking: &Booking, now: NaiveDateTime) -> ApiResult<()> {
+    if user.id != booking.ClientPersonID {
+        user.check_role_in(booking.HotelID,
+                           &[StaffRole::Receptionist, StaffRole::Manager])?;
+    }
+
+    let status = booking_status(booking);
+    if status.is_final() {
+        return Err(box ConflictError::from_str(format!("Booking {} is {}",
+                                                       booking.ID,
+                                                       status.as_str())));
+    }
+
+    if booking.DepartureTime <= now {
+        return Err(box NotAuthorizedError::from_str("Past bookings can't be changed"));
+    }
+
+    Ok(())
+}
+
+// Status column is only ever written from BookingStatus
+fn booking_status(booking: &Booking) -> BookingStatus {
+    BookingStatus::from_str(&booking.Status).unwrap()
+}
+
+pub fn check_in(req: &mut Request) -> IronResult<Response> {
+    change_status(req, BookingStatus::CheckedIn)
+}
+
+pub fn check_out(req: &mut Request) -> IronResult<Response> {
+    change_status(req, BookingStatus::CheckedOut)
+}
+
+pub fn update_status(req: &mut Request) -> IronResult<Response> {
+    let new_status: NewBookingStatus = request_body(req)?;
+
+    if new_status.Status == BookingStatus::Cancelled {
+        let detail = ErrorDetail::new("Status", "Bookings are cancelled with /cancel");
+        return Err(InvalidFieldsError::with_details("Invalid booking status", vec![detail])
+            .into());
+    }
+
+    change_status(req, new_status.Status)
+}
+
+// Only hotel staff moves bookings through their lifecycle, and gets stamped for it
+fn change_status(req: &mut Request, next: BookingStatus) -> IronResult<Response> {
+    let booking_id = req.extensions
+        .get::<Router>()
         .unwrap()
-        .into_iter()
-        .map(EmployedIn::from)
-        .last();
+        .find("id")
+        .map(|s| i32::from_str(s).unwrap())
+        .expect("No Booking ID in request");
 
-    let err_resp = NotAuthorizedError::from_str("Access denied, nor booking's owner nor hotel \
-                                                 employee")
-        .into();
-
-    if user.id == booking.ClientPersonID {
-        Ok(booking.as_response())
-    } else if let Some(employee) = employee {
-        if employee.HotelID == booking.HotelID {
-            Ok(booking.as_response())
-        } else {
-            Err(err_resp)
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::WriteBookings)?;
+
+    info!("request POST /api/booking/{}/status {{ id: {}, status: {:?} }}",
+          booking_id,
+          user.id,
+          next);
+
+    let booking = find_booking(&conn, booking_id)?;
+    user.check_role_in(booking.HotelID,
+                       &[StaffRole::Receptionist, StaffRole::Manager])?;
+
+    let status = booking_status(&booking);
+    if !status.can_become(next) {
+        return Err(ConflictError::from_str(format!("Booking {} is {}, it can't become {}",
+                                                   booking_id,
+                                                   status.as_str(),
+                                                   next.as_str()))
+            .into());
+    }
+
+    let now = chrono::UTC::now().naive_local();
+
+    match next {
+        BookingStatus::CheckedIn if now.date() < booking.ArrivalTime.date() ||
+                                    now >= booking.DepartureTime => {
+            return Err(ConflictError::from_str(format!("Guest can check in from {} to {}",
+                                                       booking.ArrivalTime.date(),
+                                                       booking.DepartureTime))
+                .into());
         }
-    } else {
-        Err(err_resp)
+        BookingStatus::NoShow if now < booking.ArrivalTime => {
+            return Err(ConflictError::from_str(format!("Guest isn't late before {}",
+                                                       booking.ArrivalTime))
+                .into());
+        }
+        _ => (),
     }
+
+    let next_str = next.as_str();
+    let status_str = status.as_str();
+    let mut query = Booking::update_builder().set("Status");
+    let mut values: Vec<&ToSql> = vec![&next_str];
+
+    match next {
+        BookingStatus::CheckedIn => {
+            query = query.set("CheckedInAt");
+            values.push(&now);
+        }
+        BookingStatus::CheckedOut => {
+            query = query.set("CheckedOutAt");
+            values.push(&now);
+        }
+        _ => (),
+    }
+
+    values.push(&booking_id);
+    values.push(&status_str);
+    let query = query.filter(format!("ID = ${} and Status = ${}", values.len() - 1, values.len()));
+
+    let trans = conn.transaction().unwrap();
+
+    if trans.execute(&query.build(), &values).unwrap() == 0 {
+        trans.finish().unwrap();
+        return Err(ConflictError::from_str(format!("Booking {} was changed meanwhile",
+                                                   booking_id))
+            .into());
+    }
+
+    trans.execute(&InsertQueryBuilder::with_template(MAINTAINED_BY_UPSERT)
+                      .table("MaintainedBy")
+                      .set("BookingID")
+                      .set("ReceptionistPersonID")
+                      .set("MaintainedAt")
+                      .build(),
+                  &[&booking_id, &user.id, &now])
+        .unwrap();
+
+    trans.commit().unwrap();
+
+    Ok(find_booking(&conn, booking_id)?.as_response())
+}
+
+pub fn booking_nights(conn: &Connection, booking_id: i32) -> Vec<BookingNight> {
+    conn.query(&BookingNight::select_builder()
+                   .filter("BookingID = $1")
+                   .order_by("Night")
+                   .build(),
+               &[&booking_id])
+        .unwrap()
+        .into_iter()
+        .map(BookingNight::from)
+        .collect::<Vec<BookingNight>>()
+}
+
+pub fn find_booking(conn: &Connection, booking_id: i32) -> ApiResult<Booking> {
+    conn.query(&Booking::select_builder()
+                   .filter("ID = $1")
+                   .build(),
+               &[&booking_id])
+        .unwrap()
+        .into_iter()
+        .map(Booking::from)
+        .last()
+        .ok_or(box NotFoundError::from_str(format!("No booking with id {}", booking_id)) as
+               Box<ApiError>)
 }
 
 pub fn put_booking(req: &mut Request) -> IronResult<Response> {
@@ -71,6 +551,7 @@ pub fn put_booking(req: &mut Request) -> IronResult<Response> {
 
     let new_booking: NewBooking = request_body(req)?;
     let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::WriteBookings)?;
 
     info!("request PUT /api/booking/ {{ id: {}, {:?} }}",
           user.id,
@@ -82,57 +563,171 @@ pub fn put_booking(req: &mut Request) -> IronResult<Response> {
         user.id
     };
 
-    let receptionist = if client_id == user.id {
-        None
-    } else {
-        let receptionist = conn.query(&SelectQueryBuilder::default()
-                       .columns("EmployedIn.PersonID, EmployedInHotelID")
-                       .from_tables("EmployedIn, Receptionist")
-                       .filter("EmployedIn.PersonID = $1 and Receptionist.PersonID = \
-                                EmployedIn.PersonID")
-                       .build(),
-                   &[&user.id])
-            .unwrap()
-            .into_iter()
-            .map(EmployedIn::from)
-            .last();
+    let on_behalf = client_id != user.id;
 
-        if receptionist.is_none() {
-            return Err(NotAuthorizedError::from_str("Only receptionist can make bookings on \
-                                                        behalf of client")
-                .into());
+    let hotel_id = if let Some(hotel_id) = new_booking.HotelID {
+        hotel_id
+    } else if on_behalf {
+        // Receptionist working in a single hotel books there
+        let hotels = user.roles
+            .HotelRoles
+            .iter()
+            .filter(|&(_, role)| *role == StaffRole::Receptionist)
+            .map(|(hotel_id, _)| *hotel_id)
+            .collect::<Vec<i32>>();
+
+        if hotels.len() != 1 {
+            return Err(NotAuthorizedError::from_str("Couldn't infer HotelID").into());
         }
 
-        receptionist
+        hotels[0]
+    } else {
+        return Err(NotAuthorizedError::from_str("Couldn't infer HotelID").into());
     };
 
-    let hotel_id = if let Some(hotel_id) = new_booking.HotelID {
-        hotel_id
-    } else if let Some(receptionist) = receptionist.as_ref() {
-        receptionist.HotelID
+    let receptionist = if on_behalf {
+        if user.role_in(hotel_id) != Some(StaffRole::Receptionist) {
+            return Err(NotAuthorizedError::from_str(format!("Only receptionist of hotel {} can \
+                                                             make bookings on behalf of client",
+                                                            hotel_id))
+                .into());
+        }
+
+        Some(user.id)
     } else {
-        return Err(NotAuthorizedError::from_str("Couldn't infer HotelID").into());
+        None
     };
 
+    validate_stay(&conn,
+                  hotel_id,
+                  new_booking.RoomNumber,
+                  new_booking.ArrivalTime,
+                  new_booking.DepartureTime)?;
+
+    check_email_verified(&conn, hotel_id, client_id)?;
+
     let current_time = chrono::UTC::now().naive_local();
-    let id = procedure::insert_booking(&conn,
-                                       client_id,
-                                       hotel_id,
-                                       new_booking.RoomNumber,
-                                       current_time,
-                                       new_booking.ArrivalTime,
-                                       new_booking.DepartureTime);
-
-    if let Some(receptionist) = receptionist {
-        conn.execute(&MaintainedBy::insert_query(),
-                     &MaintainedBy {
-                             BookingID: id,
-                             ReceptionistPersonID: receptionist.PersonID,
-                             MaintainedAt: current_time,
-                         }
-                         .insert_args())
+    let trans = conn.transaction().unwrap();
+
+    // Exclusion constraint on Booking is what actually keeps concurrent requests apart
+    let id = match procedure::insert_booking(&trans,
+                                             client_id,
+                                             hotel_id,
+                                             new_booking.RoomNumber,
+                                             current_time,
+                                             new_booking.ArrivalTime,
+                                             new_booking.DepartureTime,
+                                             None) {
+        Ok(id) => id,
+        Err(Error::Db(ref db_err)) if db_err.code == SqlState::ExclusionViolation => {
+            trans.finish().unwrap();
+            return Err(conflict_error(&conn,
+                                      hotel_id,
+                                      new_booking.RoomNumber,
+                                      new_booking.ArrivalTime,
+                                      new_booking.DepartureTime,
+                                      None)
+                .into());
+        }
+        Err(err) => panic!("{:?}", err),
+    };
+
+    if let Some(receptionist_id) = receptionist {
+        trans.execute(&MaintainedBy::insert_query(),
+                      &MaintainedBy {
+                              BookingID: id,
+                              ReceptionistPersonID: receptionist_id,
+                              MaintainedAt: current_time,
+                          }
+                          .insert_args())
             .unwrap();
     }
 
+    trans.commit().unwrap();
+
     Ok(Response::with(StatusCode::Ok))
+}
+
+pub fn check_email_verified(conn: &Connection, hotel_id: i32, client_id: i32) -> ApiResult<()> {
+    if !ruleset::hotel_rules(conn, hotel_id)?.RequireVerifiedEmail.unwrap_or(false) {
+        return Ok(());
+    }
+
+    let verified = conn.query(&Person::select_builder()
+                   .columns("EmailVerified")
+                   .filter("ID = $1")
+                   .build(),
+               &[&client_id])
+        .unwrap()
+        .into_iter()
+        .map(|row| row.get::<_, bool>("EmailVerified"))
+        .last()
+        .unwrap_or(false);
+
+    if !verified {
+        return Err(box EmailNotVerifiedError::from_str(format!("Hotel {} accepts bookings only \
+                                                                from verified accounts",
+                                                               hotel_id)));
+    }
+
+    Ok(())
+}
+
+// Dates are validated upfront so clients get a readable error rather than a constraint violation
+pub fn validate_stay(conn: &Connection,
+                     hotel_id: i32,
+                     room_number: i32,
+                     arrival: NaiveDateTime,
+                     departure: NaiveDateTime)
+                     -> ApiResult<()> {
+    if arrival >= departure {
+        let detail = ErrorDetail::new("DepartureTime", "Must be after ArrivalTime");
+        return Err(box InvalidFieldsError::with_details("Invalid stay dates", vec![detail]));
+    }
+
+    let room_exists = !conn.query(&Room::select_builder()
+                   .columns("RoomNumber")
+                   .filter("HotelID = $1 and RoomNumber = $2")
+                   .build(),
+               &[&hotel_id, &room_number])
+        .unwrap()
+        .is_empty();
+
+    if !room_exists {
+        return Err(box NotFoundError::from_str(format!("No room {} in hotel {}",
+                                                       room_number,
+                                                       hotel_id)));
+    }
+
+    Ok(())
+}
+
+// Booking being changed doesn't conflict with itself
+pub fn conflict_error(conn: &Connection,
+                      hotel_id: i32,
+                      room_number: i32,
+                      arrival: NaiveDateTime,
+                      departure: NaiveDateTime,
+                      changed_booking: Option<i32>)
+                      -> ConflictError {
+    let details = conn.query(&Booking::select_builder()
+                   .columns("ArrivalTime, DepartureTime")
+                   .filter("HotelID = $1 and RoomNumber = $2 and Cancelled = false and \
+                            ArrivalTime < $4 and DepartureTime > $3 and ID IS DISTINCT FROM $5")
+                   .order_by("ArrivalTime")
+                   .build(),
+               &[&hotel_id, &room_number, &arrival, &departure, &changed_booking])
+        .unwrap()
+        .into_iter()
+        .map(|row| {
+            ErrorDetail::new("RoomNumber",
+                             &format!("Booked from {} to {}",
+                                      row.get::<_, NaiveDateTime>("ArrivalTime"),
+                                      row.get::<_, NaiveDateTime>("DepartureTime")))
+        })
+        .collect::<Vec<ErrorDetail>>();
+
+    ConflictError::with_details(format!("Room {} is already booked for overlapping dates",
+                                        room_number),
+                                details)
 }
\ No newline at end of file
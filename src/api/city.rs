@@ -1,8 +1,12 @@
 use iron::prelude::*;
+use router::Router;
 use hyper::status::StatusCode;
+use std::str::FromStr;
 
 use super::request_body;
+use ::api::authorization::Authorizer;
 use ::proto::response::*;
+use ::proto::error::*;
 use ::proto::schema::NewCity;
 use ::db::schema::*;
 use ::db::*;
@@ -24,12 +28,49 @@ pub fn get_cities(_: &mut Request) -> IronResult<Response> {
 pub fn put_city(req: &mut Request) -> IronResult<Response> {
     let new_city: NewCity = request_body(req)?;
 
-    info!("request PUT /city {{ {:?} }}", new_city);
-
     let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_session()?;
+
+    info!("request PUT /city {{ id: {}, {:?} }}", user.id, new_city);
+
+    user.check_admin()?;
+
     let query = City::insert_query();
 
     conn.execute(&query, &[&new_city.Name]).unwrap();
 
     Ok(Response::with(StatusCode::Ok))
-}
\ No newline at end of file
+}
+
+pub fn update_city(req: &mut Request) -> IronResult<Response> {
+    let city_id = req.extensions
+        .get::<Router>()
+        .unwrap()
+        .find("id")
+        .map(|s| i32::from_str(s).unwrap())
+        .expect("No City ID in request");
+
+    let city: NewCity = request_body(req)?;
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_session()?;
+
+    info!("request POST /city/{} {{ id: {}, {:?} }}", city_id, user.id, city);
+
+    user.check_admin()?;
+
+    let updated = conn.execute(&City::update_builder()
+                     .set("Name")
+                     .filter("ID = $2")
+                     .build(),
+                 &[&city.Name, &city_id])
+        .unwrap();
+
+    if updated == 0 {
+        return Err(NotFoundError::from_str(format!("No City with id {} found", city_id)).into());
+    }
+
+    Ok(Response::with(StatusCode::Ok))
+}
@@ -75,6 +75,7 @@ pub fn get_hotel(req: &mut Request) -> IronResult<Response> {
 pub fn put_hotel(req: &mut Request) -> IronResult<Response> {
     let conn = get_db_connection();
     let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_session()?;
 
     if !user.roles.Owner {
         return Err(NotAuthorizedError::from_str("Only owner can add a hotel").into());
@@ -97,6 +98,7 @@ pub fn put_hotel(req: &mut Request) -> IronResult<Response> {
     };
 
     let hotel_id = procedure::insert_hotel(&conn, hotel);
+    Authorizer::invalidate_roles(user.id);
     ruleset::process_rules(&conn, hotel_id);
 
     Ok(Response::with(StatusCode::Ok))
@@ -112,23 +114,14 @@ pub fn update_hotel(req: &mut Request) -> IronResult<Response> {
 
     let conn = get_db_connection();
     let user = Authorizer::authorize_request(&conn, req)?;
-
-    if !user.roles.Owner && !user.roles.Manager {
-        return Err(NotAuthorizedError::from_str("Only owner and manager update hotel's info")
-            .into());
-    }
+    user.require_scope(Scope::WriteHotels)?;
 
     let update_hotel: UpdateHotel = request_body(req)?;
 
     info!("request POST /hotel/ {{ {:?} }}", update_hotel);
 
-    // Check authority in the hotel-to-update
-    if !user.roles.Owns.map_or(false, |owns| owns.contains(&hotel_id)) &&
-       !user.roles.EmployedIn.map_or(false, |emp| emp.contains(&hotel_id)) {
-        return Err(NotAuthorizedError::from_str(format!("Not owner or employee of hotel {}",
-                                                        hotel_id))
-            .into());
-    }
+    // Only owner and manager of the hotel can update its info
+    user.check_role_in(hotel_id, &[StaffRole::Manager])?;
 
     let mut update = Hotel::update_builder().filter(format!("ID = {}", hotel_id));
     let mut values: Vec<&ToSql> = Vec::with_capacity(4);
@@ -263,14 +256,10 @@ pub fn put_room(req: &mut Request) -> IronResult<Response> {
 
     let conn = get_db_connection();
     let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::WriteHotels)?;
 
     // Check authority
-    if !user.roles.Owns.map_or(false, |owns| owns.contains(&hotel_id)) &&
-       !user.roles.EmployedIn.map_or(false, |emp| emp.contains(&hotel_id)) {
-        return Err(NotAuthorizedError::from_str(format!("Not owner or employee of hotel {}",
-                                                        hotel_id))
-            .into());
-    }
+    user.check_role_in(hotel_id, &[StaffRole::Manager])?;
 
     let new_room: NewRoom = request_body(req)?;
 
@@ -305,14 +294,10 @@ pub fn update_room(req: &mut Request) -> IronResult<Response> {
 
     let conn = get_db_connection();
     let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::WriteHotels)?;
 
     // Check authority
-    if !user.roles.Owns.map_or(false, |owns| owns.contains(&hotel_id)) &&
-       !user.roles.EmployedIn.map_or(false, |emp| emp.contains(&hotel_id)) {
-        return Err(NotAuthorizedError::from_str(format!("Not owner or employee of hotel {}",
-                                                       hotel_id))
-            .into());
-    }
+    user.check_role_in(hotel_id, &[StaffRole::Manager])?;
 
     let upd_room: UpdateRoom = request_body(req)?;
 
@@ -397,6 +382,17 @@ pub fn get_employees(req: &mut Request) -> IronResult<Response> {
         .map(|val| i32::from_value(val).unwrap_or(0))
         .unwrap_or(0);
 
+    let role = match req.get_ref::<Params>()
+        .unwrap()
+        .find(&["role"])
+        .and_then(|val| String::from_value(val)) {
+        Some(role) => {
+            Some(StaffRole::from_str(&role)
+                .map_err(|err| box InvalidSchemaError::from_str(err) as Box<ApiError>)?)
+        }
+        None => None,
+    };
+
     let hotel_id = req.extensions
         .get::<Router>()
         .unwrap()
@@ -412,39 +408,102 @@ pub fn get_employees(req: &mut Request) -> IronResult<Response> {
         .unwrap_or(i32::MAX);
 
 
-    info!("request GET /hotel/{}/employees/{}?offset={}",
+    info!("request GET /hotel/{}/employees/{}?offset={}&role={:?}",
           hotel_id,
           cnt,
-          ofst);
+          ofst,
+          role);
 
     let conn = get_db_connection();
-    let persons = conn.query(&SelectQueryBuilder::default()
-                   .columns("Person.*")
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::ReadStaff)?;
+    user.check_staff_of(hotel_id)?;
+
+    let mut filter = "EmployedIn.HotelID = $1 and EmployedIn.PersonID = Person.ID".to_owned();
+    if let Some(role) = role {
+        filter.push_str(&format!(" and EmployedIn.Role = '{}'", role.as_str()));
+    }
+
+    let employees = conn.query(&SelectQueryBuilder::default()
+                   .columns("Person.ID, Person.Login, Person.Name, Person.Email, \
+                             Person.EmailVerified, EmployedIn.Role")
                    .from_tables("EmployedIn, Person")
-                   .filter("EmployedIn.HotelID = $1 and EmployedIn.PersonID = Person.ID")
+                   .filter(filter)
                    .limit(cnt)
                    .offset(ofst)
                    .build(),
                &[&hotel_id])
         .unwrap()
         .into_iter()
-        .map(Person::from)
-        .collect::<Vec<Person>>();
+        .map(|row| {
+            Employee {
+                Role: StaffRole::from_str(&row.get::<_, String>("Role"))
+                    .expect("Database is inconsistent"),
+                Person: AccountInfo::from(row),
+            }
+        })
+        .collect::<Vec<Employee>>();
+
+    Ok(employees.as_response())
+}
+
+pub fn hire_employee(req: &mut Request) -> IronResult<Response> {
+    let hotel_id = req.extensions
+        .get::<Router>()
+        .unwrap()
+        .find("id")
+        .map(|s| i32::from_str(s).unwrap())
+        .expect("No Hotel ID found in request");
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::WriteStaff)?;
+    let new_employee: NewEmployee = request_body(req)?;
+
+    info!("request PUT /hotel/{}/employee/ {{ {:?} }}",
+          hotel_id,
+          new_employee);
 
-    let mut employees = Vec::with_capacity(persons.len());
-    for person in persons.into_iter() {
-        let roles = Authorizer::get_roles(&conn, person.ID)?;
+    user.check_owner_of(hotel_id)?;
+
+    let person_exists = !conn.query(&Person::select_builder()
+                       .columns("ID")
+                       .filter("ID = $1")
+                       .build(),
+                   &[&new_employee.PersonID])
+        .unwrap()
+        .is_empty();
 
-        employees.push(Employee {
-            Person: person,
-            Roles: roles,
-        });
+    if !person_exists {
+        return Err(NotFoundError::from_str(format!("No person with id {}",
+                                                  new_employee.PersonID))
+            .into());
     }
 
-    Ok(employees.as_response())
+    // Hiring already employed person just changes the role
+    conn.execute(&InsertQueryBuilder::with_template("INSERT INTO $table ($columns) VALUES \
+                                                     ($placeholders) ON CONFLICT (PersonID, \
+                                                     HotelID) DO UPDATE SET Role = \
+                                                     excluded.Role;")
+                     .table("EmployedIn")
+                     .set("PersonID")
+                     .set("HotelID")
+                     .set("Role")
+                     .build(),
+                 &EmployedIn {
+                         PersonID: new_employee.PersonID,
+                         HotelID: hotel_id,
+                         Role: new_employee.Role.as_str().to_owned(),
+                     }
+                     .insert_args())
+        .unwrap();
+
+    Authorizer::invalidate_roles(new_employee.PersonID);
+
+    Ok(Response::with(StatusCode::Ok))
 }
 
-pub fn fire_employee(req: &mut Request) -> IronResult<Response> {
+pub fn update_employee(req: &mut Request) -> IronResult<Response> {
     let hotel_id = req.extensions
         .get::<Router>()
         .unwrap()
@@ -461,30 +520,72 @@ pub fn fire_employee(req: &mut Request) -> IronResult<Response> {
 
     let conn = get_db_connection();
     let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::WriteStaff)?;
+    let update: UpdateEmployee = request_body(req)?;
 
-    info!("request DELETE /hotel/{}/employee/{}", hotel_id, emp_id);
+    info!("request POST /hotel/{}/employee/{} {{ {:?} }}",
+          hotel_id,
+          emp_id,
+          update);
 
-    // Check authority in the hotel-to-update
-    if !user.roles.Owns.map_or(false, |owns| owns.contains(&hotel_id)) {
-        return Err(NotAuthorizedError::from_str(format!("Not owner or employee of hotel {}",
-                                                       hotel_id))
+    user.check_owner_of(hotel_id)?;
+
+    let updated = conn.execute(&EmployedIn::update_builder()
+                         .filter("PersonID = $2 and HotelID = $3")
+                         .set("Role")
+                         .build(),
+                     &[&update.Role.as_str(), &emp_id, &hotel_id])
+        .unwrap();
+
+    if updated == 0 {
+        return Err(NotFoundError::from_str(format!("Person {} is not employed in {}",
+                                                  emp_id,
+                                                  hotel_id))
             .into());
     }
 
-    let emp_roles = Authorizer::get_roles(&conn, emp_id)?;
+    Authorizer::invalidate_roles(emp_id);
+
+    Ok(Response::with(StatusCode::Ok))
+}
+
+pub fn fire_employee(req: &mut Request) -> IronResult<Response> {
+    let hotel_id = req.extensions
+        .get::<Router>()
+        .unwrap()
+        .find("id")
+        .map(|s| i32::from_str(s).unwrap())
+        .expect("No Hotel ID found in request");
+
+    let emp_id = req.extensions
+        .get::<Router>()
+        .unwrap()
+        .find("eid")
+        .map(|s| i32::from_str(s).unwrap())
+        .expect("No Employee ID found in request");
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::WriteStaff)?;
+
+    info!("request DELETE /hotel/{}/employee/{}", hotel_id, emp_id);
+
+    user.check_owner_of(hotel_id)?;
 
-    if !emp_roles.EmployedIn.map_or(false, |employed_in| employed_in.contains(&emp_id)) {
+    let fired = conn.execute(&EmployedIn::delete_builder()
+                         .filter("HotelID = $1 and PersonID = $2")
+                         .build(),
+                     &[&hotel_id, &emp_id])
+        .unwrap();
+
+    if fired == 0 {
         return Err(NotFoundError::from_str(format!("Person {} is not employed in {}",
                                                   emp_id,
                                                   hotel_id))
             .into());
     }
 
-    conn.execute(&EmployedIn::delete_builder()
-                     .filter("HotelID = $1, PersonID = $2")
-                     .build(),
-                 &[&hotel_id, &emp_id])
-        .unwrap();
+    Authorizer::invalidate_roles(emp_id);
 
     Ok(Response::with(StatusCode::Ok))
-}
\ No newline at end of file
+}
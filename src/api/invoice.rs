@@ -0,0 +1,201 @@
+use chrono;
+use iron::prelude::*;
+use router::Router;
+use params::{Params, FromValue};
+use hyper::status::StatusCode;
+use std::str::FromStr;
+use postgres::Connection;
+
+use ::api::authorization::*;
+use ::api::booking::{find_booking, booking_nights};
+use ::api::payment::booking_ledger;
+use ::api::ruleset;
+use ::invoice;
+use ::invoice::Document;
+use ::proto::schema::*;
+use ::proto::error::*;
+use ::db::schema::*;
+use ::db::*;
+
+// Booking keeps the number it got first, however many times the invoice is printed
+fn issue_invoice(conn: &Connection, hotel_id: i32, booking_id: i32) -> Invoice {
+    let trans = conn.transaction().unwrap();
+
+    // Hotel row serializes numbering, so no number is taken twice or skipped
+    trans.execute("SELECT ID FROM Hotel WHERE ID = $1 FOR UPDATE", &[&hotel_id])
+        .unwrap();
+
+    let issued = trans.query(&Invoice::select_builder()
+                   .filter("BookingID = $1")
+                   .build(),
+               &[&booking_id])
+        .unwrap()
+        .into_iter()
+        .map(Invoice::from)
+        .last();
+
+    if let Some(invoice) = issued {
+        trans.finish().unwrap();
+        return invoice;
+    }
+
+    let invoice = trans.query("INSERT INTO Invoice (HotelID, Number, BookingID, IssuedAt) \
+                               SELECT $1, COALESCE(max(Number), 0) + 1, $2, $3 FROM Invoice \
+                               WHERE HotelID = $1 RETURNING *",
+               &[&hotel_id, &booking_id, &chrono::UTC::now().naive_local()])
+        .unwrap()
+        .into_iter()
+        .map(Invoice::from)
+        .last()
+        .unwrap();
+
+    trans.commit().unwrap();
+    invoice
+}
+
+fn name_of(conn: &Connection, query: String, id: i32) -> String {
+    conn.query(&query, &[&id])
+        .unwrap()
+        .into_iter()
+        .map(|row| row.get::<_, String>("Name"))
+        .last()
+        .unwrap_or_else(|| "Unknown".to_owned())
+}
+
+fn ledger_description(entry: &LedgerEntry) -> String {
+    match entry.Method {
+        Some(ref method) => format!("{} ({})", entry.Kind, method),
+        None => entry.Kind.clone(),
+    }
+}
+
+// Printable invoice, ?format=pdf for PDF, HTML otherwise
+pub fn get_invoice(req: &mut Request) -> IronResult<Response> {
+    let booking_id = req.extensions
+        .get::<Router>()
+        .unwrap()
+        .find("id")
+        .map(|s| i32::from_str(s).unwrap())
+        .expect("No Booking ID in request");
+
+    let format = req.get_ref::<Params>()
+        .unwrap()
+        .find(&["format"])
+        .and_then(|val| String::from_value(val))
+        .unwrap_or("html".to_owned());
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::ReadBookings)?;
+
+    info!("request GET /api/booking/{}/invoice?format={} {{ id: {} }}",
+          booking_id,
+          format,
+          user.id);
+
+    if format != "html" && format != "pdf" {
+        let detail = ErrorDetail::new("format", "Must be one of html, pdf");
+        return Err(InvalidFieldsError::with_details("Invalid invoice format", vec![detail])
+            .into());
+    }
+
+    let booking = find_booking(&conn, booking_id)?;
+
+    if user.id != booking.ClientPersonID && user.check_staff_of(booking.HotelID).is_err() {
+        return Err(NotAuthorizedError::from_str("Access denied, nor booking's owner nor hotel \
+                                                 employee")
+            .into());
+    }
+
+    let tax_percentage = ruleset::hotel_rules(&conn, booking.HotelID)?
+        .TaxPercentage
+        .unwrap_or(0);
+
+    // Cancelled stay isn't charged, only the fee is
+    let lines = if booking.Cancelled {
+        vec![invoice::Line {
+                 description: "Cancellation fee".to_owned(),
+                 amount: booking.CancellationFee,
+             }]
+    } else {
+        booking_nights(&conn, booking_id)
+            .into_iter()
+            .map(|night| {
+                invoice::Line {
+                    description: format!("Night of {}, rate {}, {}% off",
+                                         night.Night,
+                                         night.BaseRate,
+                                         night.DiscountPercentage),
+                    amount: night.Price,
+                }
+            })
+            .collect()
+    };
+
+    let total = lines.iter().fold(0, |total, line| total + line.amount);
+    let ledger = booking_ledger(&*conn, booking_id);
+
+    let payments = ledger.Entries
+        .iter()
+        .filter(|entry| entry.Kind != LedgerKind::Charge.as_str())
+        .map(|entry| {
+            invoice::Payment {
+                date: entry.Created,
+                description: ledger_description(entry),
+                amount: if entry.Kind == LedgerKind::Refund.as_str() {
+                    -entry.Amount
+                } else {
+                    entry.Amount
+                },
+            }
+        })
+        .collect();
+
+    let issued = issue_invoice(&conn, booking.HotelID, booking_id);
+
+    let document = Document {
+        number: format!("{}-{:06}", issued.HotelID, issued.Number),
+        issued_at: issued.IssuedAt,
+        hotel_name: name_of(&conn,
+                            Hotel::select_builder()
+                                .columns("Name")
+                                .filter("ID = $1")
+                                .build(),
+                            booking.HotelID),
+        client_name: name_of(&conn,
+                             Person::select_builder()
+                                 .columns("Name")
+                                 .filter("ID = $1")
+                                 .build(),
+                             booking.ClientPersonID),
+        booking_id: booking_id,
+        room_number: booking.RoomNumber,
+        arrival: booking.ArrivalTime,
+        departure: booking.DepartureTime,
+        lines: lines,
+        total: total,
+        tax_percentage: tax_percentage,
+        tax: invoice::included_tax(total, tax_percentage),
+        payments: payments,
+        balance: ledger.Balance,
+    };
+
+    let mut response = if format == "pdf" {
+        let mut response = Response::with((StatusCode::Ok, document.pdf()));
+        response.headers.set_raw("Content-Type", vec![b"application/pdf".to_vec()]);
+        response.headers.set_raw("Content-Disposition",
+                                 vec![format!("inline; filename=\"invoice-{}.pdf\"",
+                                              document.number)
+                                          .into_bytes()]);
+        response
+    } else {
+        let mut response = Response::with((StatusCode::Ok, document.html()));
+        response.headers.set_raw("Content-Type", vec![b"text/html; charset=utf-8".to_vec()]);
+        response
+    };
+
+    // Balance changes with every payment, printouts must not come from cache
+    response.headers.set_raw("Cache-Control", vec![b"no-store".to_vec()]);
+
+    Ok(response)
+}
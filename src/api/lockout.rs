@@ -0,0 +1,193 @@
+use iron::prelude::*;
+use postgres::Connection;
+use params::{Params, FromValue};
+use std::cmp;
+use chrono;
+
+use ::api::authorization::Authorizer;
+use ::proto::error::*;
+use ::proto::response::*;
+use ::db::schema::*;
+use ::db::*;
+use ::config;
+
+const REPORT_DAYS: i64 = 7;
+const MAX_REPORT_DAYS: i64 = 365;
+const REPORT_LIMIT: i32 = 100;
+
+// Counter starts over when previous failure is older than $4
+const FAILURE_UPSERT: &'static str = "INSERT INTO $table ($columns) VALUES ($placeholders) ON \
+                                      CONFLICT (Key) DO UPDATE SET Failures = CASE WHEN \
+                                      SigninFailure.LastFailure < $4 THEN 1 ELSE \
+                                      SigninFailure.Failures + 1 END, LastFailure = \
+                                      excluded.LastFailure RETURNING Failures;";
+
+pub fn login_key(login: &str) -> String {
+    format!("login:{}", login)
+}
+
+fn addr_key(address: &str) -> String {
+    format!("addr:{}", address)
+}
+
+// Lockout length in seconds after given number of consecutive failures, if any
+fn backoff(failures: i32, allowed: i32, base: i64, max: i64) -> Option<i64> {
+    if failures < allowed {
+        return None;
+    }
+
+    let doublings = cmp::min(failures - allowed, 30) as u32;
+    Some(cmp::min(base.saturating_mul(2i64.pow(doublings)), max))
+}
+
+// Fails with AccountLockedError while either login or address is locked out
+pub fn check(conn: &Connection, login: &str, address: &str) -> ApiResult<()> {
+    let now = chrono::UTC::now().naive_local();
+
+    let locked_until = conn.query(&SigninFailure::select_builder()
+                   .columns("LockedUntil")
+                   .filter("Key IN ($1, $2) and LockedUntil > $3")
+                   .build(),
+               &[&login_key(login), &addr_key(address), &now])
+        .unwrap()
+        .into_iter()
+        .map(|row| row.get::<_, chrono::NaiveDateTime>("LockedUntil"))
+        .max();
+
+    match locked_until {
+        Some(until) => {
+            Err(box AccountLockedError::from_str(format!("Too many failed signins, retry in {} \
+                                                          seconds",
+                                                         (until - now).num_seconds() + 1)))
+        }
+        None => Ok(()),
+    }
+}
+
+pub fn record_failure(conn: &Connection, login: &str, address: &str) {
+    let limits = [(login_key(login), *config::SIGNIN_LOGIN_ATTEMPTS),
+                  (addr_key(address), *config::SIGNIN_ADDR_ATTEMPTS)];
+
+    for &(ref key, allowed) in &limits {
+        let now = chrono::UTC::now().naive_local();
+        let window_start = now - chrono::Duration::seconds(*config::SIGNIN_FAILURE_WINDOW);
+
+        let failures: i32 = conn.query(&InsertQueryBuilder::with_template(FAILURE_UPSERT)
+                           .table("SigninFailure")
+                           .set("Key")
+                           .set("Failures")
+                           .set("LastFailure")
+                           .build(),
+                       &[key, &1i32, &now, &window_start])
+            .unwrap()
+            .get(0)
+            .get("Failures");
+
+        if let Some(secs) = backoff(failures,
+                                    allowed,
+                                    *config::SIGNIN_LOCKOUT_BASE,
+                                    *config::SIGNIN_LOCKOUT_MAX) {
+            let locked_until = now + chrono::Duration::seconds(secs);
+
+            conn.execute(&SigninFailure::update_builder()
+                             .set("LockedUntil")
+                             .filter("Key = $2")
+                             .build(),
+                         &[&locked_until, key])
+                .unwrap();
+
+            warn!("signin locked out for {} seconds {{ {}, failures: {} }}",
+                  secs,
+                  key,
+                  failures);
+
+            let person_id = conn.query(&Person::select_builder()
+                           .columns("ID")
+                           .filter("Login = $1")
+                           .build(),
+                       &[&login])
+                .unwrap()
+                .into_iter()
+                .map(|row| row.get::<_, i32>("ID"))
+                .last();
+
+            conn.execute(&Lockout::insert_query(),
+                         &[&person_id, &login, &address, &failures, &now, &locked_until])
+                .unwrap();
+        }
+    }
+}
+
+// Successful signin forgives login failures, address ones have to expire
+pub fn reset(conn: &Connection, login: &str) {
+    conn.execute(&SigninFailure::delete_builder()
+                     .filter("Key = $1")
+                     .build(),
+                 &[&login_key(login)])
+        .unwrap();
+}
+
+// Recent lockouts of the owner and staff of owner's hotels
+pub fn get_lockouts(req: &mut Request) -> IronResult<Response> {
+    let days = req.get_ref::<Params>()
+        .unwrap()
+        .find(&["days"])
+        .map(|val| i64::from_value(val).unwrap_or(REPORT_DAYS))
+        .unwrap_or(REPORT_DAYS);
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_session()?;
+
+    info!("request GET /owner/lockouts/?days={} {{ id: {} }}", days, user.id);
+
+    if !user.roles.Owner {
+        return Err(NotAuthorizedError::from_str("Only owner can view lockouts").into());
+    }
+
+    if days < 1 || days > MAX_REPORT_DAYS {
+        let detail = ErrorDetail::new("days",
+                                      &format!("Must be between 1 and {}", MAX_REPORT_DAYS));
+        return Err(InvalidFieldsError::with_details("Invalid report period", vec![detail]).into());
+    }
+
+    let since = chrono::UTC::now().naive_local() - chrono::Duration::days(days);
+    let lockouts = conn.query(&Lockout::select_builder()
+                   .filter("LockedAt > $1 and (PersonID = $2 or PersonID IN (SELECT \
+                            EmployedIn.PersonID FROM EmployedIn, Hotel WHERE EmployedIn.HotelID \
+                            = Hotel.ID and Hotel.OwnerPersonID = $2))")
+                   .order_by("LockedAt DESC")
+                   .limit(REPORT_LIMIT)
+                   .build(),
+               &[&since, &user.id])
+        .unwrap()
+        .into_iter()
+        .map(Lockout::from)
+        .collect::<Vec<Lockout>>();
+
+    Ok(lockouts.as_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::backoff;
+
+    #[test]
+    fn free_attempts() {
+        assert_eq!(backoff(1, 5, 30, 3600), None);
+        assert_eq!(backoff(4, 5, 30, 3600), None);
+    }
+
+    #[test]
+    fn exponential() {
+        assert_eq!(backoff(5, 5, 30, 3600), Some(30));
+        assert_eq!(backoff(6, 5, 30, 3600), Some(60));
+        assert_eq!(backoff(8, 5, 30, 3600), Some(240));
+    }
+
+    #[test]
+    fn capped() {
+        assert_eq!(backoff(12, 5, 30, 3600), Some(3600));
+        assert_eq!(backoff(1000, 5, 30, 3600), Some(3600));
+    }
+}
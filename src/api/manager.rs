@@ -7,6 +7,7 @@ use std::i32;
 
 use super::request_body;
 use ::api::authorization::Authorizer;
+use ::api::ruleset;
 use ::proto::response::*;
 use ::proto::error::*;
 use ::proto::schema::*;
@@ -16,6 +17,7 @@ use ::db::*;
 pub fn get_rulesets(req: &mut Request) -> IronResult<Response> {
     let conn = get_db_connection();
     let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::ReadHotels)?;
 
     let rulesets = conn.query(&RuleSet::select_builder()
                    .filter("ManagerPersonID = $1")
@@ -32,6 +34,7 @@ pub fn get_rulesets(req: &mut Request) -> IronResult<Response> {
 pub fn get_ruleset(req: &mut Request) -> IronResult<Response> {
     let conn = get_db_connection();
     let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::ReadHotels)?;
 
     if !user.roles.Manager {
         return Err(NotAuthorizedError::from_str("Only manager can access rulesets").into());
@@ -62,12 +65,15 @@ pub fn get_ruleset(req: &mut Request) -> IronResult<Response> {
 pub fn put_ruleset(req: &mut Request) -> IronResult<Response> {
     let conn = get_db_connection();
     let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::WriteRates)?;
 
     if !user.roles.Manager {
         return Err(NotAuthorizedError::from_str("Only manager can create rulesets").into());
     }
 
     let new_ruleset: NewRuleSet = request_body(req)?;
+    ruleset::decode_rules(&new_ruleset.Body)?;
+
     let ruleset = RuleSet {
         ID: 0,
         ManagerPersonID: Some(user.id),
@@ -92,6 +98,7 @@ pub fn update_ruleset(req: &mut Request) -> IronResult<Response> {
 
     let conn = get_db_connection();
     let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::WriteRates)?;
 
     if !user.roles.Manager {
         return Err(NotAuthorizedError::from_str("Only manager can create rulesets").into());
@@ -128,19 +135,32 @@ pub fn update_ruleset(req: &mut Request) -> IronResult<Response> {
         values.push(name);
     }
 
-    if let Some(body) = update_ruleset.Body.as_ref() {
-        update = update.set("Body");
-        values.push(body);
-    }
+    let rules = match update_ruleset.Body.as_ref() {
+        Some(body) => {
+            update = update.set("Body");
+            values.push(body);
+            Some(ruleset::decode_rules(body)?)
+        }
+        None => None,
+    };
 
     // Early exit if we got empty json
     if values.is_empty() {
         return Ok(Response::with(StatusCode::Ok));
     }
 
-    conn.execute(&update.build(), &values)
+    // Hotels must never see the new body priced with the old levels
+    let trans = conn.transaction().unwrap();
+
+    trans.execute(&update.build(), &values)
         .unwrap();
 
+    if let Some(rules) = rules {
+        ruleset::reload_levels(&trans, ruleset_id, rules);
+    }
+
+    trans.commit().unwrap();
+
     Ok(Response::with(StatusCode::Ok))
 }
 
@@ -154,6 +174,7 @@ pub fn delete_ruleset(req: &mut Request) -> IronResult<Response> {
 
     let conn = get_db_connection();
     let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::WriteRates)?;
 
     let old_ruleset = conn.query(&RuleSet::select_builder()
                    .filter("ID = $1")
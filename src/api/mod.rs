@@ -1,16 +1,31 @@
 pub mod authorization;
+pub mod session;
+pub mod password;
+pub mod validation;
+pub mod apikey;
+pub mod lockout;
+pub mod audit;
+pub mod totp;
+pub mod twofactor;
 pub mod city;
 pub mod account;
 pub mod booking;
+pub mod reservation;
+pub mod payment;
+pub mod invoice;
 pub mod hotel;
 pub mod ruleset;
 pub mod manager;
 pub mod search;
+pub mod admin;
 
 #[cfg(test)]
 mod tests;
 
 use iron::prelude::*;
+use params::{Params, FromValue};
+use chrono::NaiveDateTime;
+use std::str::FromStr;
 use std::io::Read;
 use rustc_serialize::json;
 use rustc_serialize::Decodable;
@@ -19,11 +34,28 @@ use ::proto::error::*;
 fn request_body<T: Decodable>(req: &mut Request) -> ApiResult<T> {
     let mut buffer = String::with_capacity(128);
     req.body.read_to_string(&mut buffer).unwrap();
-    debug!("request body: {}", buffer);
+    // Body is not logged verbatim as it may carry credentials
+    debug!("request body: {} bytes", buffer.len());
         
     decode_json(&buffer)
 }
 
+fn datetime_param(req: &mut Request, name: &str) -> ApiResult<Option<NaiveDateTime>> {
+    let value = req.get_ref::<Params>()
+        .unwrap()
+        .find(&[name])
+        .and_then(|val| String::from_value(val));
+
+    match value.map(|value| NaiveDateTime::from_str(&value)) {
+        Some(Ok(datetime)) => Ok(Some(datetime)),
+        Some(Err(_)) => {
+            let detail = ErrorDetail::new(name, "Must look like 2016-01-31T12:00:00");
+            Err(box InvalidFieldsError::with_details("Invalid time range", vec![detail]))
+        }
+        None => Ok(None),
+    }
+}
+
 fn decode_json<T: Decodable>(json: &str) -> ApiResult<T> {
     json::decode(json).map_err(|err| box InvalidSchemaError::from(err) as Box<ApiError>)
 }
\ No newline at end of file
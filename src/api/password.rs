@@ -0,0 +1,124 @@
+use crypto::digest::Digest;
+use crypto::hmac::Hmac;
+use crypto::pbkdf2::pbkdf2;
+use crypto::sha2::Sha256;
+use crypto::util::fixed_time_eq;
+use rand::{OsRng, Rng};
+use rustc_serialize::hex::{ToHex, FromHex};
+
+use ::config;
+
+// Stored as "pbkdf2_sha256$<iterations>$<hex salt>$<hex hash>"
+const ALGORITHM: &'static str = "pbkdf2_sha256";
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verification {
+    Valid,
+    // Password matches, but stored hash is legacy or uses outdated parameters
+    NeedsUpgrade,
+    Invalid,
+}
+
+pub fn hash(password: &str) -> String {
+    hash_with(password, *config::PASSWORD_HASH_ITERATIONS)
+}
+
+pub fn verify(password: &str, stored: &str) -> Verification {
+    let parts = stored.split('$').collect::<Vec<&str>>();
+
+    if parts.len() != 4 || parts[0] != ALGORITHM {
+        // Legacy rows hold whatever the client used to send verbatim
+        return if fixed_time_eq(password.as_bytes(), stored.as_bytes()) {
+            Verification::NeedsUpgrade
+        } else {
+            Verification::Invalid
+        };
+    }
+
+    let iterations = match parts[1].parse::<u32>() {
+        Ok(iterations) => iterations,
+        Err(_) => return Verification::Invalid,
+    };
+
+    let (salt, expected) = match (parts[2].from_hex(), parts[3].from_hex()) {
+        (Ok(salt), Ok(expected)) => (salt, expected),
+        _ => return Verification::Invalid,
+    };
+
+    if !fixed_time_eq(&derive(password, &salt, iterations, expected.len()), &expected) {
+        Verification::Invalid
+    } else if iterations < *config::PASSWORD_HASH_ITERATIONS {
+        Verification::NeedsUpgrade
+    } else {
+        Verification::Valid
+    }
+}
+
+// Burns the same amount of time as a real verification,
+// so unknown logins can't be told apart by response time
+pub fn verify_dummy(password: &str) {
+    derive(password, &[0u8; SALT_LEN], *config::PASSWORD_HASH_ITERATIONS, HASH_LEN);
+}
+
+// Fast digest for high-entropy secrets like API keys and recovery codes,
+// which don't need key stretching
+pub fn token_digest(token: &str) -> String {
+    let mut sha = Sha256::new();
+    sha.input_str(token);
+    sha.result_str()
+}
+
+fn hash_with(password: &str, iterations: u32) -> String {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng::new()
+        .expect("Failed to open OS random number generator")
+        .fill_bytes(&mut salt);
+
+    let hash = derive(password, &salt, iterations, HASH_LEN);
+
+    format!("{}${}${}${}", ALGORITHM, iterations, salt.to_hex(), hash.to_hex())
+}
+
+fn derive(password: &str, salt: &[u8], iterations: u32, len: usize) -> Vec<u8> {
+    let mut mac = Hmac::new(Sha256::new(), password.as_bytes());
+    let mut output = vec![0u8; len];
+    pbkdf2(&mut mac, salt, iterations, &mut output);
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::hash_with;
+    use ::config;
+
+    #[test]
+    fn verify_hashed() {
+        let stored = hash("secret");
+
+        assert_eq!(verify("secret", &stored), Verification::Valid);
+        assert_eq!(verify("Secret", &stored), Verification::Invalid);
+    }
+
+    #[test]
+    fn salted() {
+        assert!(hash("secret") != hash("secret"));
+    }
+
+    #[test]
+    fn upgrade_legacy() {
+        assert_eq!(verify("0", "0"), Verification::NeedsUpgrade);
+        assert_eq!(verify("1", "0"), Verification::Invalid);
+    }
+
+    #[test]
+    fn upgrade_weak() {
+        let stored = hash_with("secret", *config::PASSWORD_HASH_ITERATIONS / 2);
+
+        assert_eq!(verify("secret", &stored), Verification::NeedsUpgrade);
+        assert_eq!(verify("Secret", &stored), Verification::Invalid);
+    }
+}
@@ -0,0 +1,281 @@
+use chrono;
+use iron::prelude::*;
+use router::Router;
+use std::str::FromStr;
+use postgres::GenericConnection;
+
+use super::request_body;
+use ::api::authorization::*;
+use ::api::booking::find_booking;
+use ::payment;
+use ::payment::{Charge, PaymentError};
+use ::proto::schema::*;
+use ::proto::response::*;
+use ::proto::error::*;
+use ::db::schema::*;
+use ::db::*;
+
+fn booking_id(req: &Request) -> i32 {
+    req.extensions
+        .get::<Router>()
+        .unwrap()
+        .find("id")
+        .map(|s| i32::from_str(s).unwrap())
+        .expect("No Booking ID in request")
+}
+
+pub fn booking_ledger(conn: &GenericConnection, booking_id: i32) -> BookingLedger {
+    let entries = conn.query(&LedgerEntry::select_builder()
+                   .filter("BookingID = $1")
+                   .order_by("ID")
+                   .build(),
+               &[&booking_id])
+        .unwrap()
+        .into_iter()
+        .map(LedgerEntry::from)
+        .collect::<Vec<LedgerEntry>>();
+
+    let balance = conn.query("SELECT booking_balance($1)", &[&booking_id])
+        .unwrap()
+        .get(0)
+        .get(0);
+
+    BookingLedger {
+        Entries: entries,
+        Balance: balance,
+    }
+}
+
+// Payments, deposits and refunds minus refunds already made
+fn received(conn: &GenericConnection, booking_id: i32) -> i64 {
+    conn.query(&LedgerEntry::select_builder()
+                   .columns("COALESCE(sum(CASE WHEN Kind = 'Refund' THEN -Amount ELSE Amount \
+                             END), 0) AS Received")
+                   .filter("BookingID = $1 and Kind <> 'Charge'")
+                   .build(),
+               &[&booking_id])
+        .unwrap()
+        .get(0)
+        .get("Received")
+}
+
+fn payment_error(err: PaymentError) -> Box<ApiError> {
+    match err {
+        PaymentError::Declined(_) => box PaymentDeclinedError::from_str(format!("{}", err)),
+        PaymentError::Unavailable(_) => {
+            error!("payment gateway failed: {}", err);
+            box PaymentGatewayError::from_str(format!("{}", err))
+        }
+    }
+}
+
+pub fn get_payments(req: &mut Request) -> IronResult<Response> {
+    let booking_id = booking_id(req);
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::ReadBookings)?;
+
+    info!("request GET /api/booking/{}/payments {{ id: {} }}",
+          booking_id,
+          user.id);
+
+    let booking = find_booking(&conn, booking_id)?;
+
+    if user.id != booking.ClientPersonID && user.check_staff_of(booking.HotelID).is_err() {
+        return Err(NotAuthorizedError::from_str("Access denied, nor booking's owner nor hotel \
+                                                 employee")
+            .into());
+    }
+
+    Ok(booking_ledger(&*conn, booking_id).as_response())
+}
+
+// Desk records money it took or gave back, online refunds go back through the gateway
+pub fn record_payment(req: &mut Request) -> IronResult<Response> {
+    let booking_id = booking_id(req);
+    let new_payment: NewPayment = request_body(req)?;
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::WriteBookings)?;
+
+    info!("request POST /api/booking/{}/payments {{ id: {}, {:?} }}",
+          booking_id,
+          user.id,
+          new_payment);
+
+    let booking = find_booking(&conn, booking_id)?;
+    user.check_role_in(booking.HotelID,
+                       &[StaffRole::Receptionist, StaffRole::Manager])?;
+
+    let mut details = Vec::new();
+
+    if new_payment.Kind == LedgerKind::Charge {
+        details.push(ErrorDetail::new("Kind", "Charges are recorded as booking's cost changes"));
+    }
+
+    if new_payment.Method == PaymentMethod::Online && new_payment.Kind != LedgerKind::Refund {
+        details.push(ErrorDetail::new("Method", "Guests pay online by themselves"));
+    }
+
+    if new_payment.Amount <= 0 {
+        details.push(ErrorDetail::new("Amount", "Must be positive"));
+    }
+
+    if !details.is_empty() {
+        return Err(InvalidFieldsError::with_details("Invalid payment", details).into());
+    }
+
+    let trans = conn.transaction().unwrap();
+
+    // Concurrent refunds must not give back more than was paid
+    trans.execute("SELECT ID FROM Booking WHERE ID = $1 FOR UPDATE", &[&booking_id])
+        .unwrap();
+
+    let mut reference = new_payment.Reference.clone();
+    let mut refund_of = None;
+
+    if new_payment.Kind == LedgerKind::Refund {
+        if new_payment.Amount as i64 > received(&trans, booking_id) {
+            let detail = ErrorDetail::new("Amount", "Can't refund more than was paid");
+            return Err(InvalidFieldsError::with_details("Invalid payment", vec![detail]).into());
+        }
+
+        if new_payment.Method == PaymentMethod::Online {
+            let charge = match new_payment.Reference {
+                Some(ref charge_reference) => {
+                    trans.query(&LedgerEntry::select_builder()
+                                  .filter("BookingID = $1 and Kind = 'Payment' and Method = \
+                                           'Online' and Reference = $2")
+                                  .build(),
+                              &[&booking_id, charge_reference])
+                        .unwrap()
+                        .into_iter()
+                        .map(LedgerEntry::from)
+                        .next()
+                }
+                None => None,
+            };
+
+            let charge = match charge {
+                Some(charge) => charge,
+                None => {
+                    let detail = ErrorDetail::new("Reference",
+                                                  "Must be reference of online payment for this \
+                                                   booking");
+                    return Err(InvalidFieldsError::with_details("Invalid payment", vec![detail])
+                        .into());
+                }
+            };
+
+            // Gateway can't give back more than that very charge took
+            let refunded: i64 = trans.query(&LedgerEntry::select_builder()
+                                                .columns("COALESCE(sum(Amount), 0) AS Refunded")
+                                                .filter("RefundOf = $1")
+                                                .build(),
+                                            &[&charge.ID])
+                .unwrap()
+                .get(0)
+                .get("Refunded");
+
+            if new_payment.Amount as i64 > charge.Amount as i64 - refunded {
+                let detail = ErrorDetail::new("Amount",
+                                              "Can't refund more than was left of the payment");
+                return Err(InvalidFieldsError::with_details("Invalid payment", vec![detail])
+                    .into());
+            }
+
+            refund_of = Some(charge.ID);
+
+            let receipt = payment::gateway()
+                .refund(new_payment.Reference.as_ref().unwrap(), new_payment.Amount)
+                .map_err(payment_error)?;
+
+            reference = Some(receipt.reference);
+        }
+    }
+
+    trans.execute(&LedgerEntry::insert_query(),
+                  &LedgerEntry {
+                          ID: 0,
+                          BookingID: booking_id,
+                          Kind: new_payment.Kind.as_str().to_owned(),
+                          Amount: new_payment.Amount,
+                          Method: Some(new_payment.Method.as_str().to_owned()),
+                          Reference: reference,
+                          RefundOf: refund_of,
+                          RecordedBy: Some(user.id),
+                          Created: chrono::UTC::now().naive_local(),
+                      }
+                      .insert_args())
+        .unwrap();
+
+    let ledger = booking_ledger(&trans, booking_id);
+    trans.commit().unwrap();
+
+    Ok(ledger.as_response())
+}
+
+// Charges the card for the whole outstanding balance
+pub fn pay_online(req: &mut Request) -> IronResult<Response> {
+    let booking_id = booking_id(req);
+    let online_payment: OnlinePayment = request_body(req)?;
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::WriteBookings)?;
+
+    info!("request POST /api/booking/{}/pay {{ id: {}, {:?} }}",
+          booking_id,
+          user.id,
+          online_payment);
+
+    let booking = find_booking(&conn, booking_id)?;
+
+    if user.id != booking.ClientPersonID {
+        return Err(NotAuthorizedError::from_str("Only the guest can pay for booking online")
+            .into());
+    }
+
+    let trans = conn.transaction().unwrap();
+
+    // Guest double clicking must not be charged twice
+    trans.execute("SELECT ID FROM Booking WHERE ID = $1 FOR UPDATE", &[&booking_id])
+        .unwrap();
+
+    let balance = booking_ledger(&trans, booking_id).Balance;
+
+    if balance <= 0 {
+        return Err(ConflictError::from_str(format!("Booking {} has nothing to pay", booking_id))
+            .into());
+    }
+
+    let receipt = payment::gateway()
+        .charge(&Charge {
+            booking_id: booking_id,
+            amount: balance,
+            card_token: online_payment.CardToken,
+        })
+        .map_err(payment_error)?;
+
+    trans.execute(&LedgerEntry::insert_query(),
+                  &LedgerEntry {
+                          ID: 0,
+                          BookingID: booking_id,
+                          Kind: LedgerKind::Payment.as_str().to_owned(),
+                          Amount: balance,
+                          Method: Some(PaymentMethod::Online.as_str().to_owned()),
+                          Reference: Some(receipt.reference),
+                          RefundOf: None,
+                          RecordedBy: Some(user.id),
+                          Created: chrono::UTC::now().naive_local(),
+                      }
+                      .insert_args())
+        .unwrap();
+
+    let ledger = booking_ledger(&trans, booking_id);
+    trans.commit().unwrap();
+
+    Ok(ledger.as_response())
+}
@@ -0,0 +1,207 @@
+use chrono;
+use iron::prelude::*;
+use router::Router;
+use std::str::FromStr;
+use postgres::Connection;
+use postgres::error::{Error, SqlState};
+
+use super::request_body;
+use ::api::authorization::*;
+use ::api::booking::{validate_stay, conflict_error, check_email_verified, MAINTAINED_BY_UPSERT};
+use ::proto::schema::*;
+use ::proto::response::*;
+use ::proto::error::*;
+use ::db::schema::*;
+use ::db::schemaext::*;
+use ::db::*;
+
+fn reservation_details(conn: &Connection, reservation: Reservation) -> ReservationDetails {
+    let bookings = conn.query(&Booking::select_builder()
+                   .filter("ReservationID = $1")
+                   .order_by("ID")
+                   .build(),
+               &[&reservation.ID])
+        .unwrap()
+        .into_iter()
+        .map(Booking::from)
+        .collect::<Vec<Booking>>();
+
+    let full_cost = bookings.iter().fold(0, |cost, booking| {
+        cost + if booking.Cancelled {
+            booking.CancellationFee
+        } else {
+            booking.FullCost
+        }
+    });
+
+    ReservationDetails {
+        Reservation: reservation,
+        Bookings: bookings,
+        FullCost: full_cost,
+    }
+}
+
+// Exclusion constraint would reject these too, but without telling which rooms clash
+fn check_rooms(rooms: &[ReservationRoom]) -> ApiResult<()> {
+    if rooms.is_empty() {
+        let detail = ErrorDetail::new("Rooms", "Must contain at least one room");
+        return Err(box InvalidFieldsError::with_details("Invalid reservation", vec![detail]));
+    }
+
+    let mut details = Vec::new();
+
+    for (i, room) in rooms.iter().enumerate() {
+        let overlaps = rooms[i + 1..].iter().any(|other| {
+            other.RoomNumber == room.RoomNumber && other.ArrivalTime < room.DepartureTime &&
+            other.DepartureTime > room.ArrivalTime
+        });
+
+        if overlaps {
+            details.push(ErrorDetail::new("Rooms",
+                                          &format!("Room {} is requested twice for \
+                                                    overlapping dates",
+                                                   room.RoomNumber)));
+        }
+    }
+
+    if !details.is_empty() {
+        return Err(box InvalidFieldsError::with_details("Invalid reservation", details));
+    }
+
+    Ok(())
+}
+
+pub fn get_reservation(req: &mut Request) -> IronResult<Response> {
+    let reservation_id = req.extensions
+        .get::<Router>()
+        .unwrap()
+        .find("id")
+        .map(|s| i32::from_str(s).unwrap())
+        .expect("No Reservation ID in request");
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::ReadBookings)?;
+
+    info!("request GET /api/reservation/{} {{ id: {} }}",
+          reservation_id,
+          user.id);
+
+    let reservation = conn.query(&Reservation::select_builder()
+                   .filter("ID = $1")
+                   .build(),
+               &[&reservation_id])
+        .unwrap()
+        .into_iter()
+        .map(Reservation::from)
+        .last()
+        .ok_or(box NotFoundError::from_str(format!("No reservation with id {}", reservation_id)) as
+               Box<ApiError>)?;
+
+    if user.id != reservation.LeadPersonID &&
+       user.check_staff_of(reservation.HotelID).is_err() {
+        return Err(NotAuthorizedError::from_str("Access denied, nor reservation's lead guest nor \
+                                                 hotel employee")
+            .into());
+    }
+
+    Ok(reservation_details(&conn, reservation).as_response())
+}
+
+// Books every room under one reservation, or none of them
+pub fn put_reservation(req: &mut Request) -> IronResult<Response> {
+    let conn = get_db_connection();
+
+    let new_reservation: NewReservation = request_body(req)?;
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_scope(Scope::WriteBookings)?;
+
+    info!("request PUT /api/reservation/ {{ id: {}, {:?} }}",
+          user.id,
+          new_reservation);
+
+    let hotel_id = new_reservation.HotelID;
+    let client_id = new_reservation.ClientPersonID.unwrap_or(user.id);
+    let on_behalf = client_id != user.id;
+
+    if on_behalf && user.role_in(hotel_id) != Some(StaffRole::Receptionist) {
+        return Err(NotAuthorizedError::from_str(format!("Only receptionist of hotel {} can make \
+                                                         reservations on behalf of client",
+                                                        hotel_id))
+            .into());
+    }
+
+    check_rooms(&new_reservation.Rooms)?;
+
+    for room in &new_reservation.Rooms {
+        validate_stay(&conn,
+                      hotel_id,
+                      room.RoomNumber,
+                      room.ArrivalTime,
+                      room.DepartureTime)?;
+    }
+
+    check_email_verified(&conn, hotel_id, client_id)?;
+
+    let current_time = chrono::UTC::now().naive_local();
+    let trans = conn.transaction().unwrap();
+
+    let reservation_id: i32 = trans.query(&InsertQueryBuilder::with_template("INSERT INTO $table \
+                                                                  ($columns) VALUES \
+                                                                  ($placeholders) RETURNING ID;")
+                       .table("Reservation")
+                       .set("LeadPersonID")
+                       .set("HotelID")
+                       .set("CreatedAt")
+                       .build(),
+                   &[&client_id, &hotel_id, &current_time])
+        .unwrap()
+        .get(0)
+        .get("ID");
+
+    for room in &new_reservation.Rooms {
+        let id = match procedure::insert_booking(&trans,
+                                                 client_id,
+                                                 hotel_id,
+                                                 room.RoomNumber,
+                                                 current_time,
+                                                 room.ArrivalTime,
+                                                 room.DepartureTime,
+                                                 Some(reservation_id)) {
+            Ok(id) => id,
+            Err(Error::Db(ref db_err)) if db_err.code == SqlState::ExclusionViolation => {
+                trans.finish().unwrap();
+                return Err(conflict_error(&conn,
+                                          hotel_id,
+                                          room.RoomNumber,
+                                          room.ArrivalTime,
+                                          room.DepartureTime,
+                                          None)
+                    .into());
+            }
+            Err(err) => panic!("{:?}", err),
+        };
+
+        if on_behalf {
+            trans.execute(&InsertQueryBuilder::with_template(MAINTAINED_BY_UPSERT)
+                              .table("MaintainedBy")
+                              .set("BookingID")
+                              .set("ReceptionistPersonID")
+                              .set("MaintainedAt")
+                              .build(),
+                          &[&id, &user.id, &current_time])
+                .unwrap();
+        }
+    }
+
+    trans.commit().unwrap();
+
+    let reservation = Reservation {
+        ID: reservation_id,
+        LeadPersonID: client_id,
+        HotelID: hotel_id,
+        CreatedAt: current_time,
+    };
+
+    Ok(reservation_details(&conn, reservation).as_response())
+}
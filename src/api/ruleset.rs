@@ -5,8 +5,10 @@ use postgres::types::ToSql;
 use std::str::FromStr;
 use std::i32;
 use rustc_serialize::json;
-use postgres::Connection;
+use postgres::{Connection, GenericConnection};
 use postgres::error::{Error, DbError, SqlState};
+use chrono::NaiveDateTime;
+use std::cmp;
 
 use super::request_body;
 use super::decode_json;
@@ -101,7 +103,10 @@ lazy_static!(
                 Discount: 10,
                 BookingsAmount: 50
             }
-        ]
+        ],
+        RequireVerifiedEmail: None,
+        Cancellation: None,
+        TaxPercentage: None
     };
 );
 
@@ -109,32 +114,43 @@ lazy_static!(
 
 
 pub fn process_rules(conn: &Connection, hotel_id: i32) -> ApiResult<()> {
-    let hotel_ruleset_id = conn.query(&Hotel::select_builder()
-                   .filter("ID = $1")
-                   .build(),
-               &[&hotel_id])
-        .unwrap()
-        .into_iter()
-        .last()
-        .map(Hotel::from)
-        .map(|hotel| hotel.RuleSetID)
-        .ok_or(box NotFoundError::from_str("No such Hotel") as Box<ApiError>)?;
+    let (hotel_ruleset_id, ruleset) = hotel_ruleset(conn, hotel_id)?;
+    insert_levels(conn, hotel_ruleset_id, ruleset);
 
-    let ruleset: Rules = conn.query(&RuleSet::select_builder()
-                   .filter("ID = $1")
-                   .build(),
-               &[&hotel_ruleset_id])
-        .unwrap()
-        .into_iter()
-        .last()
-        .map(RuleSet::from)
-        .map(|rset| decode_json(&rset.Body))
-        .ok_or(box NotFoundError::from_str("No such RuleSet") as Box<ApiError>)??;
+    Ok(())
+}
+
+// Levels of already processed ruleset are stale once its body changes
+pub fn reload_levels(conn: &GenericConnection, ruleset_id: i32, ruleset: Rules) {
+    conn.execute(&schema::RoomLevel::delete_builder()
+                     .filter("RuleSetID = $1")
+                     .build(),
+                 &[&ruleset_id])
+        .unwrap();
+
+    conn.execute(&schema::ClientLevel::delete_builder()
+                     .filter("RuleSetID = $1")
+                     .build(),
+                 &[&ruleset_id])
+        .unwrap();
+
+    insert_levels(conn, ruleset_id, ruleset);
+}
+
+fn insert_levels(conn: &GenericConnection, ruleset_id: i32, ruleset: Rules) {
+    // Violating the key would abort the caller's transaction, so repeats are skipped up front
+    let mut room_levels = Vec::with_capacity(ruleset.RoomLevels.len());
+    let mut client_levels = Vec::with_capacity(ruleset.ClientLevels.len());
 
     for room_level in ruleset.RoomLevels {
+        if room_levels.contains(&room_level.Level) {
+            continue;
+        }
+        room_levels.push(room_level.Level);
+
         let db_room_level = schema::RoomLevel {
             Level: room_level.Level,
-            RuleSetID: hotel_ruleset_id,
+            RuleSetID: ruleset_id,
             LevelName: room_level.Name,
             PerNight: room_level.PerNight,
         };
@@ -153,9 +169,14 @@ pub fn process_rules(conn: &Connection, hotel_id: i32) -> ApiResult<()> {
     }
 
     for client_level in ruleset.ClientLevels {
+        if client_levels.contains(&client_level.BookingsAmount) {
+            continue;
+        }
+        client_levels.push(client_level.BookingsAmount);
+
         let db_client_level = schema::ClientLevel {
             BookingsAmount: client_level.BookingsAmount,
-            RuleSetID: hotel_ruleset_id,
+            RuleSetID: ruleset_id,
             LevelName: client_level.Name,
             DiscountPercentage: client_level.Discount,
         };
@@ -171,14 +192,84 @@ pub fn process_rules(conn: &Connection, hotel_id: i32) -> ApiResult<()> {
             Err(err) => panic!("{:?}", err)
         }
     }
+}
+
+// Cancellation window above ten years is surely a typo
+const MAX_FREE_UNTIL_DAYS: i64 = 3650;
+
+// Rulesets are checked when saved, not every time a booking is priced
+pub fn decode_rules(body: &str) -> ApiResult<Rules> {
+    let rules: Rules = decode_json(body)?;
+
+    if let Some(ref policy) = rules.Cancellation {
+        if policy.FreeUntilDays < 0 || policy.FreeUntilDays > MAX_FREE_UNTIL_DAYS {
+            let detail = ErrorDetail::new("Cancellation.FreeUntilDays",
+                                          &format!("Must be between 0 and {}",
+                                                   MAX_FREE_UNTIL_DAYS));
+            return Err(box InvalidFieldsError::with_details("Invalid ruleset", vec![detail]));
+        }
+    }
 
-    Ok(())
+    Ok(rules)
+}
+
+pub fn hotel_rules(conn: &Connection, hotel_id: i32) -> ApiResult<Rules> {
+    hotel_ruleset(conn, hotel_id).map(|(_, rules)| rules)
+}
+
+fn hotel_ruleset(conn: &Connection, hotel_id: i32) -> ApiResult<(i32, Rules)> {
+    let hotel_ruleset_id = conn.query(&Hotel::select_builder()
+                   .filter("ID = $1")
+                   .build(),
+               &[&hotel_id])
+        .unwrap()
+        .into_iter()
+        .last()
+        .map(Hotel::from)
+        .map(|hotel| hotel.RuleSetID)
+        .ok_or(box NotFoundError::from_str("No such Hotel") as Box<ApiError>)?;
+
+    let ruleset: Rules = conn.query(&RuleSet::select_builder()
+                   .filter("ID = $1")
+                   .build(),
+               &[&hotel_ruleset_id])
+        .unwrap()
+        .into_iter()
+        .last()
+        .map(RuleSet::from)
+        .map(|rset| decode_json(&rset.Body))
+        .ok_or(box NotFoundError::from_str("No such RuleSet") as Box<ApiError>)??;
+
+    Ok((hotel_ruleset_id, ruleset))
 }
 
 #[derive(Debug, RustcDecodable, RustcEncodable)]
-struct Rules {
+pub struct Rules {
     RoomLevels: Vec<RoomLevel>,
     ClientLevels: Vec<ClientLevel>,
+    pub RequireVerifiedEmail: Option<bool>,
+    // Without a policy bookings can be cancelled for free
+    pub Cancellation: Option<CancellationPolicy>,
+    // Share of prices that is tax, shown on invoices
+    pub TaxPercentage: Option<i32>,
+}
+
+// Free until FreeUntilDays before arrival, FeePercentage of the full cost after that
+#[derive(Debug, Clone, RustcDecodable, RustcEncodable)]
+pub struct CancellationPolicy {
+    pub FreeUntilDays: i64,
+    pub FeePercentage: i32,
+}
+
+impl CancellationPolicy {
+    pub fn fee(&self, full_cost: i32, arrival: NaiveDateTime, now: NaiveDateTime) -> i32 {
+        if (arrival - now).num_days() >= self.FreeUntilDays {
+            return 0;
+        }
+
+        let percentage = cmp::max(0, cmp::min(self.FeePercentage, 100)) as i64;
+        (full_cost as i64 * percentage / 100) as i32
+    }
 }
 
 #[derive(Debug, RustcDecodable, RustcEncodable)]
@@ -194,3 +285,57 @@ struct ClientLevel {
     Discount: i32,
     BookingsAmount: i32,
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use super::{CancellationPolicy, decode_rules};
+
+    #[test]
+    fn cancellation_fee() {
+        let policy = CancellationPolicy {
+            FreeUntilDays: 3,
+            FeePercentage: 50,
+        };
+        let arrival = NaiveDate::from_ymd(2016, 6, 10).and_hms(14, 0, 0);
+
+        assert_eq!(policy.fee(1000, arrival, NaiveDate::from_ymd(2016, 6, 1).and_hms(0, 0, 0)), 0);
+        assert_eq!(policy.fee(1000, arrival, NaiveDate::from_ymd(2016, 6, 7).and_hms(14, 0, 0)), 0);
+        assert_eq!(policy.fee(1000, arrival, NaiveDate::from_ymd(2016, 6, 7).and_hms(15, 0, 0)),
+                   500);
+        assert_eq!(policy.fee(1000, arrival, NaiveDate::from_ymd(2016, 6, 11).and_hms(0, 0, 0)),
+                   500);
+    }
+
+    #[test]
+    fn cancellation_fee_bounds() {
+        let arrival = NaiveDate::from_ymd(2016, 6, 10).and_hms(14, 0, 0);
+        let now = NaiveDate::from_ymd(2016, 6, 10).and_hms(0, 0, 0);
+
+        let policy = |percentage| {
+            CancellationPolicy {
+                FreeUntilDays: 1,
+                FeePercentage: percentage,
+            }
+        };
+
+        assert_eq!(policy(150).fee(1000, arrival, now), 1000);
+        assert_eq!(policy(-10).fee(1000, arrival, now), 0);
+    }
+
+    #[test]
+    fn cancellation_window_bounds() {
+        let rules = |days: &str| {
+            decode_rules(&format!("{{\"RoomLevels\":[],\"ClientLevels\":[],\
+                                   \"Cancellation\":{{\"FreeUntilDays\":{},\
+                                   \"FeePercentage\":50}}}}",
+                                  days))
+        };
+
+        assert!(rules("0").is_ok());
+        assert!(rules("3650").is_ok());
+        assert!(rules("-1").is_err());
+        assert!(rules("3651").is_err());
+        assert!(rules("9223372036854775807").is_err());
+    }
+}
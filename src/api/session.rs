@@ -0,0 +1,174 @@
+use chrono;
+use postgres::Connection;
+
+use ::api::authorization::{Token, new_token};
+use ::db::schema::Session;
+use ::db::*;
+use ::config;
+
+// Sessions younger than this are not touched on every request
+const LAST_SEEN_RESOLUTION_SECS: i64 = 60;
+
+pub trait SessionStore {
+    fn create(&self, person_id: i32, user_agent: Option<String>, pending_2fa: bool) -> Session;
+    fn find(&self, token: &str) -> Option<Session>;
+    fn find_pending(&self, token: &str) -> Option<Session>;
+    fn confirm(&self, session: &Session) -> Session;
+    fn list(&self, person_id: i32) -> Vec<Session>;
+    fn revoke(&self, token: &str);
+    fn revoke_by_id(&self, person_id: i32, session_id: i32) -> bool;
+    fn revoke_all(&self, person_id: i32);
+}
+
+pub struct PgSessionStore<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> PgSessionStore<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        PgSessionStore { conn: conn }
+    }
+
+    fn purge_expired(&self) {
+        self.conn
+            .execute(&Session::delete_builder()
+                         .filter("Expires <= $1")
+                         .build(),
+                     &[&chrono::UTC::now().naive_local()])
+            .unwrap();
+    }
+}
+
+impl<'a> SessionStore for PgSessionStore<'a> {
+    // Pending session is short-lived and not accepted until confirmed
+    fn create(&self, person_id: i32, user_agent: Option<String>, pending_2fa: bool) -> Session {
+        self.purge_expired();
+
+        let now = chrono::UTC::now().naive_local();
+        let ttl = if pending_2fa {
+            *config::TWO_FACTOR_PENDING_TTL
+        } else {
+            *config::TOKEN_TTL
+        };
+
+        let token: Token = new_token();
+        let session = Session {
+            ID: 0,
+            Token: token,
+            PersonID: person_id,
+            Created: now,
+            LastSeen: now,
+            Expires: now + chrono::Duration::seconds(ttl),
+            UserAgent: user_agent,
+            Pending2FA: pending_2fa,
+        };
+
+        self.conn
+            .execute(&Session::insert_query(), &session.insert_args())
+            .unwrap();
+
+        session
+    }
+
+    fn find(&self, token: &str) -> Option<Session> {
+        let now = chrono::UTC::now().naive_local();
+        let session = self.conn
+            .query(&Session::select_builder()
+                       .filter("Token = $1 and Expires > $2 and Pending2FA = false")
+                       .build(),
+                   &[&token, &now])
+            .unwrap()
+            .into_iter()
+            .map(Session::from)
+            .last();
+
+        if let Some(session) = session.as_ref() {
+            if now - session.LastSeen > chrono::Duration::seconds(LAST_SEEN_RESOLUTION_SECS) {
+                self.conn
+                    .execute(&Session::update_builder()
+                                 .filter(format!("ID = {}", session.ID))
+                                 .set("LastSeen")
+                                 .build(),
+                             &[&now])
+                    .unwrap();
+            }
+        }
+
+        session
+    }
+
+    fn find_pending(&self, token: &str) -> Option<Session> {
+        self.conn
+            .query(&Session::select_builder()
+                       .filter("Token = $1 and Expires > $2 and Pending2FA = true")
+                       .build(),
+                   &[&token, &chrono::UTC::now().naive_local()])
+            .unwrap()
+            .into_iter()
+            .map(Session::from)
+            .last()
+    }
+
+    // Turns pending session into a regular one with full lifetime
+    fn confirm(&self, session: &Session) -> Session {
+        let now = chrono::UTC::now().naive_local();
+        let expires = now + chrono::Duration::seconds(*config::TOKEN_TTL);
+
+        self.conn
+            .execute(&Session::update_builder()
+                         .set("Pending2FA")
+                         .set("LastSeen")
+                         .set("Expires")
+                         .filter("ID = $4")
+                         .build(),
+                     &[&false, &now, &expires, &session.ID])
+            .unwrap();
+
+        Session {
+            LastSeen: now,
+            Expires: expires,
+            Pending2FA: false,
+            ..session.clone()
+        }
+    }
+
+    fn list(&self, person_id: i32) -> Vec<Session> {
+        self.conn
+            .query(&Session::select_builder()
+                       .filter("PersonID = $1 and Expires > $2 and Pending2FA = false")
+                       .order_by("LastSeen DESC")
+                       .build(),
+                   &[&person_id, &chrono::UTC::now().naive_local()])
+            .unwrap()
+            .into_iter()
+            .map(Session::from)
+            .collect()
+    }
+
+    fn revoke(&self, token: &str) {
+        self.conn
+            .execute(&Session::delete_builder()
+                         .filter("Token = $1")
+                         .build(),
+                     &[&token])
+            .unwrap();
+    }
+
+    fn revoke_by_id(&self, person_id: i32, session_id: i32) -> bool {
+        self.conn
+            .execute(&Session::delete_builder()
+                         .filter("ID = $1 and PersonID = $2")
+                         .build(),
+                     &[&session_id, &person_id])
+            .unwrap() != 0
+    }
+
+    fn revoke_all(&self, person_id: i32) {
+        self.conn
+            .execute(&Session::delete_builder()
+                         .filter("PersonID = $1")
+                         .build(),
+                     &[&person_id])
+            .unwrap();
+    }
+}
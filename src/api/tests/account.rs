@@ -4,10 +4,13 @@ use hyper::header::{SetCookie, CookiePair};
 use hyper::status::StatusCode;
 use rustc_serialize::json;
 use rand;
+use chrono;
 
 use super::random_str;
+use super::random_email;
 use super::response_body;
 use super::authorization::*;
+use ::api::totp;
 use ::proto::schema::*;
 use ::db::schemaext::*;
 use ::db::schema::*;
@@ -17,7 +20,7 @@ use ::db::*;
 fn get_account_info() {
     let login = random_str();
     let name = random_str();
-    let email = random_str();
+    let email = random_email();
     let passhash = random_str();
 
     let token = signup_with(&login, &name, &email, &passhash);
@@ -48,13 +51,13 @@ fn account_info(token: String) -> AccountInfo {
 fn update_account_info() {
     let login = random_str();
     let name = random_str();
-    let email = random_str();
+    let email = random_email();
     let passhash = random_str();
 
     let token = signup_with(&login, &name, &email, &passhash);
     
     let new_name = random_str();
-    let new_email = random_str();
+    let new_email = random_email();
     let new_passhash = random_str();
 
     let client = hyper::Client::new();
@@ -72,8 +75,8 @@ fn update_account_info() {
     let update = UpdateAccountInfoData {
         NewName: Some(new_name.clone()),
         NewEmail: Some(new_email.clone()),
-        OldPassHash: None,
-        NewPassHash: None
+        OldPassword: None,
+        NewPassword: None
     };
 
     let mut res = client.post("http://localhost:8080/api/account/")
@@ -92,8 +95,8 @@ fn update_account_info() {
     let update = UpdateAccountInfoData {
         NewName: None,
         NewEmail: None,
-        OldPassHash: None,
-        NewPassHash: Some(new_passhash.clone())
+        OldPassword: None,
+        NewPassword: Some(new_passhash.clone())
     };
 
     let mut res = client.post("http://localhost:8080/api/account/")
@@ -104,12 +107,49 @@ fn update_account_info() {
 
     assert_eq!(res.status, StatusCode::Forbidden);
 
+    // Too short password is rejected like on signup
+    let update = UpdateAccountInfoData {
+        NewName: None,
+        NewEmail: None,
+        OldPassword: Some(passhash.clone()),
+        NewPassword: Some("1".to_owned())
+    };
+
+    let mut res = client.post("http://localhost:8080/api/account/")
+        .sign(token.clone())
+        .body(&json::encode(&update).unwrap())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+    assert!(response_body(&mut res).contains("\"field\":\"NewPassword\""));
+
+    // Password stays the same when the rest of the update fails
+    let taken_email = random_email();
+    signup_with(&random_str(), &random_str(), &taken_email, &random_str());
+
+    let update = UpdateAccountInfoData {
+        NewName: None,
+        NewEmail: Some(taken_email),
+        OldPassword: Some(passhash.clone()),
+        NewPassword: Some(new_passhash.clone())
+    };
+
+    let res = client.post("http://localhost:8080/api/account/")
+        .sign(token.clone())
+        .body(&json::encode(&update).unwrap())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+    signin_with(&login, &passhash);
+
     // Update password
     let update = UpdateAccountInfoData {
         NewName: None,
         NewEmail: None,
-        OldPassHash: Some(passhash.clone()),
-        NewPassHash: Some(new_passhash.clone())
+        OldPassword: Some(passhash.clone()),
+        NewPassword: Some(new_passhash.clone())
     };
 
     let mut res = client.post("http://localhost:8080/api/account/")
@@ -124,11 +164,319 @@ fn update_account_info() {
     signin_with(&login, &new_passhash);
 }
 
+#[test]
+fn sessions() {
+    let login = random_str();
+    let passhash = random_str();
+
+    let token = signup_with(&login, &random_str(), &random_email(), &passhash);
+    let (other_token, _) = signin_with(&login, &passhash);
+
+    let client = hyper::Client::new();
+    let mut res = client.get("http://localhost:8080/api/account/sessions/")
+        .sign(token.clone())
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    println!("body:    {:?}", resp_body);
+    assert_eq!(res.status, StatusCode::Ok);
+
+    let sessions: Vec<SessionInfo> = json::decode(&resp_body).unwrap();
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions.iter().filter(|session| session.Current).count(), 1);
+
+    // Revoke the other session
+    let other = sessions.into_iter()
+        .filter(|session| !session.Current)
+        .last()
+        .unwrap();
+
+    let res = client.delete(&format!("http://localhost:8080/api/account/sessions/{}", other.ID))
+        .sign(token.clone())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+
+    let res = client.get("http://localhost:8080/api/account/")
+        .sign(other_token)
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+}
+
+#[test]
+fn password_reset() {
+    let login = random_str();
+    let token = signup_with(&login, &random_str(), &random_email(), &random_str());
+
+    let client = hyper::Client::new();
+    let res = client.post("http://localhost:8080/api/account/password-reset/")
+        .body(&format!("{{ \"Login\":\"{}\" }}", login))
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+
+    // Token is delivered by mail, so take it straight from the database
+    let reset_token: String = get_db_connection()
+        .query("SELECT PasswordReset.Token FROM PasswordReset, Person \
+                WHERE PasswordReset.PersonID = Person.ID and Person.Login = $1 \
+                and PasswordReset.Used = false",
+               &[&login])
+        .unwrap()
+        .get(0)
+        .get(0);
+
+    let mut res = client.post("http://localhost:8080/api/account/password-reset/confirm")
+        .body(&json::encode(&PasswordResetConfirm {
+            Token: reset_token.clone(),
+            NewPassword: "1".to_owned(),
+        }).unwrap())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+    assert!(response_body(&mut res).contains("\"field\":\"NewPassword\""));
+
+    // Rejected password doesn't use the token up
+    let new_password = random_str();
+    let confirm = PasswordResetConfirm {
+        Token: reset_token,
+        NewPassword: new_password.clone(),
+    };
+
+    let res = client.post("http://localhost:8080/api/account/password-reset/confirm")
+        .body(&json::encode(&confirm).unwrap())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+
+    // Token is single-use
+    let res = client.post("http://localhost:8080/api/account/password-reset/confirm")
+        .body(&json::encode(&confirm).unwrap())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+
+    // Old sessions are revoked
+    let res = client.get("http://localhost:8080/api/account/")
+        .sign(token)
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+
+    signin_with(&login, &new_password);
+}
+
+#[test]
+fn verify_email() {
+    let login = random_str();
+    let token = signup_with(&login, &random_str(), &random_email(), &random_str());
+
+    assert!(!account_info(token.clone()).EmailVerified);
+
+    // Token is delivered by mail, so take it straight from the database
+    let verification_token: String = get_db_connection()
+        .query("SELECT EmailVerification.Token FROM EmailVerification, Person \
+                WHERE EmailVerification.PersonID = Person.ID and Person.Login = $1 \
+                and EmailVerification.Used = false",
+               &[&login])
+        .unwrap()
+        .get(0)
+        .get(0);
+
+    let client = hyper::Client::new();
+    let res = client.post("http://localhost:8080/api/account/verify-email/confirm")
+        .body(&json::encode(&EmailVerificationConfirm { Token: verification_token }).unwrap())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+    assert!(account_info(token).EmailVerified);
+}
+
+#[test]
+fn two_factor() {
+    let login = random_str();
+    let passhash = random_str();
+    let token = create_owner(&login, &passhash);
+
+    let client = hyper::Client::new();
+    let mut res = client.post("http://localhost:8080/api/account/2fa/")
+        .sign(token.clone())
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    assert_eq!(res.status, StatusCode::Ok);
+    let enrollment: TwoFactorEnrollment = json::decode(&resp_body).unwrap();
+    assert!(enrollment.Uri.starts_with("otpauth://totp/"));
+
+    let secret = totp::base32_decode(&enrollment.Secret).unwrap();
+    let now = chrono::UTC::now().timestamp();
+
+    let mut res = client.post("http://localhost:8080/api/account/2fa/confirm")
+        .body(&json::encode(&TwoFactorCode { Code: totp::code_at(&secret, now) }).unwrap())
+        .sign(token)
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    assert_eq!(res.status, StatusCode::Ok);
+    let recovery: RecoveryCodes = json::decode(&resp_body).unwrap();
+
+    let signin = || {
+        let mut res = hyper::Client::new()
+            .post("http://localhost:8080/api/signin/")
+            .body(&format!("{{ \"Login\":\"{}\", \"Password\":\"{}\"}}", login, passhash))
+            .send()
+            .unwrap();
+
+        assert_eq!(res.status, StatusCode::Ok);
+        let signin: SigninResponse = json::decode(&response_body(&mut res)).unwrap();
+        assert!(signin.Pending2FA && signin.Roles.is_none());
+
+        signin.Token
+    };
+
+    let second_factor = |token: String, code: String| {
+        hyper::Client::new()
+            .post("http://localhost:8080/api/signin/2fa")
+            .body(&json::encode(&TwoFactorSignin {
+                Token: token,
+                Code: code,
+            }).unwrap())
+            .send()
+            .unwrap()
+            .status
+    };
+
+    // Pending token is good for nothing else
+    let pending = signin();
+    let res = client.get("http://localhost:8080/api/account/")
+        .sign(pending.clone())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+
+    // Code used on enrollment can't be replayed, the next one is fine
+    assert_eq!(second_factor(pending.clone(), totp::code_at(&secret, now)),
+               StatusCode::Forbidden);
+    assert_eq!(second_factor(pending.clone(), totp::code_at(&secret, now + totp::STEP_SECS)),
+               StatusCode::Ok);
+    account_info(pending);
+
+    // Recovery codes work once
+    let recovery_code = recovery.RecoveryCodes[0].clone();
+    assert_eq!(second_factor(signin(), recovery_code.clone()), StatusCode::Ok);
+    assert_eq!(second_factor(signin(), recovery_code), StatusCode::Forbidden);
+
+    forgive_address_failures();
+}
+
+#[test]
+fn export_and_delete() {
+    let login = random_str();
+    let email = random_email();
+    let passhash = random_str();
+
+    let token = signup_with(&login, &random_str(), &email, &passhash);
+
+    let client = hyper::Client::new();
+    let mut res = client.get("http://localhost:8080/api/account/export")
+        .sign(token.clone())
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    assert_eq!(res.status, StatusCode::Ok);
+    assert!(res.headers.get_raw("Content-Disposition").is_some());
+
+    let export: AccountExport = json::decode(&resp_body).unwrap();
+    assert_eq!(export.Profile.Login, login);
+    assert_eq!(export.Profile.Email, email);
+    assert!(export.Bookings.is_empty());
+
+    let delete = |password: &str| {
+        client.delete("http://localhost:8080/api/account/")
+            .body(&json::encode(&AccountDeletion { Password: password.to_owned() }).unwrap())
+            .sign(token.clone())
+            .send()
+            .unwrap()
+            .status
+    };
+
+    assert_eq!(delete("wrong"), StatusCode::Forbidden);
+    assert_eq!(delete(&passhash), StatusCode::Ok);
+
+    // Sessions are gone along with the credentials
+    let res = client.get("http://localhost:8080/api/account/")
+        .sign(token.clone())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+
+    let res = client.post("http://localhost:8080/api/signin/")
+        .body(&format!("{{ \"Login\":\"{}\", \"Password\":\"{}\"}}", login, passhash))
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+
+    // Login and email are free to be taken again
+    signup_with(&login, &random_str(), &email, &passhash);
+
+    forgive_address_failures();
+}
+
+#[test]
+fn deletion_scrubs_audit() {
+    let login = random_str();
+    let passhash = random_str();
+
+    signup_with(&login, &random_str(), &random_email(), &passhash);
+    let (token, roles) = signin_with(&login, &passhash);
+
+    let res = hyper::Client::new()
+        .delete("http://localhost:8080/api/account/")
+        .body(&json::encode(&AccountDeletion { Password: passhash }).unwrap())
+        .sign(token)
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+
+    let mut res = hyper::Client::new()
+        .get(&format!("http://localhost:8080/api/audit/auth/?person={}", roles.ID))
+        .sign(signin_admin())
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    assert_eq!(res.status, StatusCode::Ok);
+    assert!(!resp_body.contains(&login));
+
+    // Events themselves stay, only who was behind them is gone
+    let events: Vec<AuthEvent> = json::decode(&resp_body).unwrap();
+    assert_eq!(events.first().unwrap().Kind, "AccountDeleted");
+    assert!(events.iter().all(|event| {
+        event.Login.is_none() && event.Address.is_none() && event.UserAgent.is_none()
+    }));
+}
+
 #[test]
 fn get_bookings() {
     let login = random_str();
     let name = random_str();
-    let email = random_str();
+    let email = random_email();
     let passhash = random_str();
 
     let token = signup_with(&login, &name, &email, &passhash);
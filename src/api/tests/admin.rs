@@ -0,0 +1,106 @@
+use hyper;
+use hyper::status::StatusCode;
+use rustc_serialize::json;
+
+use super::response_body;
+use super::random_str;
+use super::random_email;
+use super::authorization::*;
+use super::hotel::create_hotel;
+use ::proto::schema::*;
+use ::db::schemaext::*;
+
+#[test]
+fn admin() {
+    let client = hyper::Client::new();
+    let admin_token = signin_admin();
+
+    let login = random_str();
+    let pass = random_str();
+    signup_with(&login, &random_str(), &random_email(), &pass);
+    let (token, roles) = signin_with(&login, &pass);
+    assert!(!roles.Owner);
+
+    // Ordinary users can't manage other accounts
+    let res = client.post(&format!("http://localhost:8080/api/admin/person/{}", roles.ID))
+        .body(&json::encode(&AdminUpdatePerson {
+            Name: None,
+            Email: None,
+            EmailVerified: None,
+            Owner: Some(true),
+            Admin: None,
+        }).unwrap())
+        .sign(token)
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+
+    let res = client.post(&format!("http://localhost:8080/api/admin/person/{}", roles.ID))
+        .body(&json::encode(&AdminUpdatePerson {
+            Name: None,
+            Email: None,
+            EmailVerified: Some(true),
+            Owner: Some(true),
+            Admin: None,
+        }).unwrap())
+        .sign(admin_token.clone())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+
+    let (_, roles) = signin_with(&login, &pass);
+    assert!(roles.Owner);
+
+    let mut res = client.get("http://localhost:8080/api/admin/persons/")
+        .sign(admin_token.clone())
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    assert_eq!(res.status, StatusCode::Ok);
+
+    let persons: Vec<AccountInfo> = json::decode(&resp_body).unwrap();
+    assert!(persons.iter().any(|person| person.Login == login && person.EmailVerified));
+
+    // Hotel moves to the new owner
+    let hotel = create_hotel(signin_owner());
+    let res = client.post(&format!("http://localhost:8080/api/admin/hotel/{}/owner", hotel.ID))
+        .body(&json::encode(&HotelOwner { OwnerPersonID: roles.ID }).unwrap())
+        .sign(admin_token.clone())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+
+    let (_, roles) = signin_with(&login, &pass);
+    assert!(roles.Owns.as_ref().unwrap().contains(&hotel.ID));
+
+    // Owner role stays while the person still owns hotels, and nothing else changes either
+    let new_name = random_str();
+    let res = client.post(&format!("http://localhost:8080/api/admin/person/{}", roles.ID))
+        .body(&json::encode(&AdminUpdatePerson {
+            Name: Some(new_name.clone()),
+            Email: None,
+            EmailVerified: None,
+            Owner: Some(false),
+            Admin: None,
+        }).unwrap())
+        .sign(admin_token.clone())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+
+    let (_, roles) = signin_with(&login, &pass);
+    assert!(roles.Owner);
+
+    let mut res = client.get("http://localhost:8080/api/admin/persons/")
+        .sign(admin_token)
+        .send()
+        .unwrap();
+
+    let persons: Vec<AccountInfo> = json::decode(&response_body(&mut res)).unwrap();
+    assert!(persons.iter().any(|person| person.Login == login && person.Name != new_name));
+}
@@ -0,0 +1,128 @@
+use hyper;
+use hyper::header::Authorization;
+use hyper::status::StatusCode;
+use rustc_serialize::json;
+
+use super::response_body;
+use super::authorization::*;
+use super::hotel::create_hotel;
+use ::proto::schema::*;
+
+#[test]
+fn api_key() {
+    let owner_token = signin_owner();
+    let hotel_a = create_hotel(owner_token.clone());
+    let hotel_b = create_hotel(owner_token.clone());
+
+    let client = hyper::Client::new();
+    let mut res = client.put("http://localhost:8080/api/apikey/")
+        .body(&json::encode(&NewApiKey {
+            Name: "channel-sync".to_owned(),
+            HotelIDs: vec![hotel_a.ID],
+            Scopes: vec![Scope::ReadStaff],
+        }).unwrap())
+        .sign(owner_token.clone())
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    assert_eq!(res.status, StatusCode::Ok);
+    let created: CreatedApiKey = json::decode(&resp_body).unwrap();
+
+    let with_key = |method: hyper::method::Method, url: String| {
+        hyper::Client::new()
+            .request(method, &url)
+            .header(Authorization(format!("Bearer {}", created.Key)))
+            .send()
+            .unwrap()
+            .status
+    };
+
+    // Key is limited to its hotels and scopes
+    assert_eq!(with_key(hyper::method::Method::Get,
+                        format!("http://localhost:8080/api/hotel/{}/employees/", hotel_a.ID)),
+               StatusCode::Ok);
+    assert_eq!(with_key(hyper::method::Method::Get,
+                        format!("http://localhost:8080/api/hotel/{}/employees/", hotel_b.ID)),
+               StatusCode::Forbidden);
+    assert_eq!(with_key(hyper::method::Method::Delete,
+                        format!("http://localhost:8080/api/hotel/{}/employee/{}", hotel_a.ID, 1)),
+               StatusCode::Forbidden);
+
+    // Nor can it manage credentials
+    assert_eq!(with_key(hyper::method::Method::Get,
+                        "http://localhost:8080/api/account/sessions/".to_owned()),
+               StatusCode::Forbidden);
+    assert_eq!(with_key(hyper::method::Method::Get,
+                        "http://localhost:8080/api/apikeys/".to_owned()),
+               StatusCode::Forbidden);
+
+    let mut res = client.get("http://localhost:8080/api/apikeys/")
+        .sign(owner_token.clone())
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    assert_eq!(res.status, StatusCode::Ok);
+    let keys: Vec<ApiKeyInfo> = json::decode(&resp_body).unwrap();
+    let key = keys.into_iter().find(|key| key.ID == created.ID).unwrap();
+    assert_eq!(key.HotelIDs, vec![hotel_a.ID]);
+    assert!(key.LastUsed.is_some());
+
+    let res = client.delete(&format!("http://localhost:8080/api/apikey/{}", created.ID))
+        .sign(owner_token)
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+    assert_eq!(with_key(hyper::method::Method::Get,
+                        format!("http://localhost:8080/api/hotel/{}/employees/", hotel_a.ID)),
+               StatusCode::Forbidden);
+}
+
+#[test]
+fn api_key_foreign_hotel() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(signin_owner());
+
+    let res = hyper::Client::new()
+        .put("http://localhost:8080/api/apikey/")
+        .body(&json::encode(&NewApiKey {
+            Name: "reporting".to_owned(),
+            HotelIDs: vec![hotel.ID],
+            Scopes: vec![Scope::ReadBookings],
+        }).unwrap())
+        .sign(owner_token)
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+}
+
+#[test]
+fn api_key_name() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+
+    let mut long_name = String::new();
+    for _ in 0..65 {
+        long_name.push('k');
+    }
+
+    for name in vec!["".to_owned(), "   ".to_owned(), long_name] {
+        let mut res = hyper::Client::new()
+            .put("http://localhost:8080/api/apikey/")
+            .body(&json::encode(&NewApiKey {
+                Name: name,
+                HotelIDs: vec![hotel.ID],
+                Scopes: vec![Scope::ReadBookings],
+            }).unwrap())
+            .sign(owner_token.clone())
+            .send()
+            .unwrap();
+
+        let resp_body = response_body(&mut res);
+        assert_eq!(res.status, StatusCode::Forbidden);
+        assert!(resp_body.contains("\"field\":\"Name\""));
+    }
+}
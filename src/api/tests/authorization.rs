@@ -1,14 +1,18 @@
 use hyper;
 use hyper::client::RequestBuilder;
-use hyper::header::{Cookie, SetCookie, CookiePair};
+use hyper::header::{Authorization, Cookie, Headers, SetCookie, CookiePair};
 use hyper::status::StatusCode;
 use rustc_serialize::json;
 use rand;
 
 use super::random_str;
+use super::random_email;
 use super::response_body;
+use super::hotel::{create_hotel, hire};
+use ::proto::error::ErrorCode;
 use ::proto::schema::*;
 use ::db::schema::*;
+use ::db::schemaext::*;
 use ::db::*;
 
 #[test]
@@ -16,6 +20,59 @@ pub fn signup() {
     signup_random();    
 }
 
+#[test]
+fn signup_validation() {
+    let signup = |login: &str, name: &str, email: &str| {
+        let mut res = hyper::Client::new()
+            .post("http://localhost:8080/api/signup/")
+            .body(&json::encode(&SignupData {
+                Login: login.to_owned(),
+                Name: name.to_owned(),
+                Email: email.to_owned(),
+                Password: random_str(),
+            }).unwrap())
+            .send()
+            .unwrap();
+
+        let resp_body = response_body(&mut res);
+        println!("body:    {:?}", resp_body);
+
+        assert_eq!(res.status, StatusCode::Forbidden);
+        resp_body
+    };
+
+    // All invalid fields are reported at once
+    let body = signup("a b", " ", "nobody");
+    assert!(body.contains(&format!("\"err_code\":\"{}\"", ErrorCode::InvalidFieldsError as i32)));
+    assert!(body.contains("\"field\":\"Login\""));
+    assert!(body.contains("\"field\":\"Name\""));
+    assert!(body.contains("\"field\":\"Email\""));
+
+    let mut res = hyper::Client::new()
+        .post("http://localhost:8080/api/signup/")
+        .body(&json::encode(&SignupData {
+            Login: random_str(),
+            Name: random_str(),
+            Email: random_email(),
+            Password: "1".to_owned(),
+        }).unwrap())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+    assert!(response_body(&mut res).contains("\"field\":\"Password\""));
+
+    let login = random_str();
+    let email = random_email();
+    signup_with(&login, &random_str(), &email, &random_str());
+
+    let body = signup(&login, &random_str(), &random_email());
+    assert!(body.contains(&format!("\"err_code\":\"{}\"", ErrorCode::LoginTakenError as i32)));
+
+    let body = signup(&random_str(), &random_str(), &email);
+    assert!(body.contains(&format!("\"err_code\":\"{}\"", ErrorCode::EmailTakenError as i32)));
+}
+
 #[test]
 fn signin() {
     let login = format!("{}", random_str());
@@ -23,16 +80,219 @@ fn signin() {
     let email = format!("{}", random_str());
     let passhash = format!("{}", random_str());
 
+    // Legacy row storing the credential verbatim
     get_db_connection().execute(&Person::insert_query(), &Person {
         ID:       0,
         Login:    login.clone(),
         Name:     name,
         Email:    email,
         PassHash: passhash.clone(),
+        EmailVerified: false,
     }.insert_args())
      .unwrap();
 
     signin_with(&login, &passhash);
+
+    // Must be transparently upgraded to salted hash
+    let stored: String = get_db_connection()
+        .query("SELECT PassHash FROM Person WHERE Login = $1", &[&login])
+        .unwrap()
+        .get(0)
+        .get(0);
+
+    assert!(stored.starts_with("pbkdf2_sha256$"));
+    signin_with(&login, &passhash);
+}
+
+#[test]
+fn signout() {
+    let token = signup_random();
+
+    let client = hyper::Client::new();
+    let res = client.post("http://localhost:8080/api/signout/")
+        .sign(token.clone())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+
+    // Revoked token must not be accepted anymore
+    let res = client.get("http://localhost:8080/api/account/")
+        .sign(token)
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+}
+
+#[test]
+fn forged_token() {
+    let login = random_str();
+    let passhash = random_str();
+    signup_with(&login, &random_str(), &random_email(), &passhash);
+    let (_, roles) = signin_with(&login, &passhash);
+
+    // Person ID used to be a valid token
+    let client = hyper::Client::new();
+    let res = client.get("http://localhost:8080/api/account/")
+        .sign(roles.ID.to_string())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+}
+
+#[test]
+fn header_token() {
+    let login = random_str();
+    let passhash = random_str();
+    signup_with(&login, &random_str(), &random_email(), &passhash);
+    let (token, roles) = signin_with(&login, &passhash);
+
+    let client = hyper::Client::new();
+    let mut res = client.get("http://localhost:8080/api/account/")
+        .header(Authorization(format!("Bearer {}", token)))
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+    let info: AccountInfo = json::decode(&response_body(&mut res)).unwrap();
+    assert_eq!(info.ID, roles.ID);
+
+    let mut headers = Headers::new();
+    headers.set_raw("token", vec![token.into_bytes()]);
+    let res = client.get("http://localhost:8080/api/account/")
+        .headers(headers)
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+
+    let res = client.get("http://localhost:8080/api/account/")
+        .header(Authorization("Bearer forged".to_owned()))
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+}
+
+#[test]
+fn lockout() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+
+    let login = random_str();
+    let passhash = random_str();
+    signup_with(&login, &random_str(), &random_email(), &passhash);
+    let (_, roles) = signin_with(&login, &passhash);
+    hire(owner_token.clone(), hotel.ID, roles.ID, StaffRole::Cleaner);
+
+    let signin = |passwd: &str| {
+        let mut res = hyper::Client::new()
+            .post("http://localhost:8080/api/signin/")
+            .body(&format!("{{ \"Login\":\"{}\", \"Password\":\"{}\"}}", login, passwd))
+            .send()
+            .unwrap();
+
+        (res.status, response_body(&mut res))
+    };
+
+    for _ in 0..5 {
+        assert_eq!(signin("wrong").0, StatusCode::Forbidden);
+    }
+
+    // Even the right password is rejected while locked out
+    let (status, body) = signin(&passhash);
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&format!("\"err_code\":\"{}\"", ErrorCode::AccountLockedError as i32)));
+
+    let mut res = hyper::Client::new()
+        .get("http://localhost:8080/api/owner/lockouts/")
+        .sign(owner_token.clone())
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    assert_eq!(res.status, StatusCode::Ok);
+    let lockouts: Vec<Lockout> = json::decode(&resp_body).unwrap();
+    assert!(lockouts.iter().any(|lockout| lockout.PersonID == Some(roles.ID)));
+
+    // Report period is bounded, huge ones would overflow
+    for days in &["0", "-1", "366", "9223372036854775807"] {
+        let mut res = hyper::Client::new()
+            .get(&format!("http://localhost:8080/api/owner/lockouts/?days={}", days))
+            .sign(owner_token.clone())
+            .send()
+            .unwrap();
+
+        let resp_body = response_body(&mut res);
+        assert_eq!(res.status, StatusCode::Forbidden);
+        assert!(resp_body.contains(&format!("\"err_code\":\"{}\"",
+                                            ErrorCode::InvalidFieldsError as i32)));
+    }
+
+    forgive_address_failures();
+}
+
+#[test]
+fn auth_events() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+
+    let login = random_str();
+    let passhash = random_str();
+    let staff_token = signup_with(&login, &random_str(), &random_email(), &passhash);
+    let (_, roles) = signin_with(&login, &passhash);
+    hire(owner_token.clone(), hotel.ID, roles.ID, StaffRole::Receptionist);
+
+    let res = hyper::Client::new()
+        .post("http://localhost:8080/api/signin/")
+        .body(&format!("{{ \"Login\":\"{}\", \"Password\":\"wrong\"}}", login))
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+
+    let events = |token: String, query: &str| {
+        let mut res = hyper::Client::new()
+            .get(&format!("http://localhost:8080/api/audit/auth/?{}", query))
+            .sign(token)
+            .send()
+            .unwrap();
+
+        let resp_body = response_body(&mut res);
+        println!("body:    {:?}", resp_body);
+
+        (res.status, resp_body)
+    };
+
+    let (status, body) = events(owner_token.clone(), &format!("person={}", roles.ID));
+    assert_eq!(status, StatusCode::Ok);
+
+    let kinds = json::decode::<Vec<AuthEvent>>(&body)
+        .unwrap()
+        .into_iter()
+        .map(|event| event.Kind)
+        .collect::<Vec<String>>();
+
+    assert_eq!(kinds, vec!["SigninFailed", "Signin", "Signup"]);
+
+    // Staff can't look at anyone's events
+    let (status, _) = events(staff_token, "");
+    assert_eq!(status, StatusCode::Forbidden);
+
+    let (status, body) = events(owner_token, "from=yesterday");
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&format!("\"err_code\":\"{}\"", ErrorCode::InvalidFieldsError as i32)));
+
+    forgive_address_failures();
+}
+
+// Don't let failures of one test lock out the others sharing the address
+pub fn forgive_address_failures() {
+    get_db_connection()
+        .execute("DELETE FROM SigninFailure WHERE Key LIKE 'addr:%'", &[])
+        .unwrap();
 }
 
 pub trait SignedRequest {
@@ -52,7 +312,7 @@ impl<'a> SignedRequest for RequestBuilder<'a> {
 pub fn signin_with(login: &str, passwd: &str) -> (String, Roles) {
     let client = hyper::Client::new();
     let mut res = client.post("http://localhost:8080/api/signin/")
-        .body(&format!("{{ \"Login\":\"{}\", \"PassHash\":\"{}\"}}", login, passwd))
+        .body(&format!("{{ \"Login\":\"{}\", \"Password\":\"{}\"}}", login, passwd))
         .send()
         .unwrap();
     
@@ -68,17 +328,18 @@ pub fn signin_with(login: &str, passwd: &str) -> (String, Roles) {
 
     println!("token: {:?}", token.value);
 
-    let roles: Roles = json::decode(&resp_body).unwrap();
+    let signin: SigninResponse = json::decode(&resp_body).unwrap();
     assert_eq!(res.status, StatusCode::Ok);
+    assert_eq!(signin.Token, token.value);
 
-    (token.value.to_owned(), roles)
+    (signin.Token, signin.Roles.unwrap())
 }
 
 pub fn signup_with(login: &str, name: &str, email: &str, passhash: &str) -> String {
     let client = hyper::Client::new();
     let mut res = client.post("http://localhost:8080/api/signup/")
         .body(&format!("{{ \"Login\":\"{}\", \"Name\":\"{}\", \"Email\":\"{}\", \
-                 \"PassHash\":\"{}\"}}", login, name, email, passhash))
+                 \"Password\":\"{}\"}}", login, name, email, passhash))
         .send()
         .unwrap();
     
@@ -94,22 +355,38 @@ pub fn signup_with(login: &str, name: &str, email: &str, passhash: &str) -> Stri
 
     println!("token: {:?}", token.value);
 
-    let roles: Roles = json::decode(&resp_body).unwrap();
+    let signin: SigninResponse = json::decode(&resp_body).unwrap();
     assert_eq!(res.status, StatusCode::Ok);
+    assert_eq!(signin.Token, token.value);
 
-    token.value.to_owned()
+    signin.Token
 }
 
 pub fn signup_random() -> String {
-    signup_with(&random_str(), &random_str(), &random_str(), &random_str())    
+    signup_with(&random_str(), &random_str(), &random_email(), &random_str())    
 }
 
 pub fn signin_owner() -> String {
     create_owner(&random_str(), &random_str())
 }
 
-fn create_owner(login: &str, pass: &str) -> String {
-    signup_with(login, &random_str(), &random_str(), pass);
+pub fn signin_admin() -> String {
+    let login = random_str();
+    let pass = random_str();
+    signup_with(&login, &random_str(), &random_email(), &pass);
+    let (_, roles) = signin_with(&login, &pass);
+
+    get_db_connection()
+        .execute(&Admin::insert_query(), &Admin { PersonID: roles.ID }.insert_args())
+        .unwrap();
+
+    let (token, roles) = signin_with(&login, &pass);
+    assert!(roles.Admin);
+    token
+}
+
+pub fn create_owner(login: &str, pass: &str) -> String {
+    signup_with(login, &random_str(), &random_email(), pass);
     let (token, roles) = signin_with(login, pass);
 
     let conn = get_db_connection();
@@ -0,0 +1,691 @@
+use hyper;
+use hyper::header::Authorization;
+use hyper::status::StatusCode;
+use rustc_serialize::json;
+use chrono;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use std::thread;
+
+use super::response_body;
+use super::random_str;
+use super::random_email;
+use super::authorization::*;
+use super::hotel::{create_hotel, create_room, hire};
+use super::payment::payments;
+use ::proto::error::ErrorCode;
+use ::proto::schema::*;
+use ::db::schema::*;
+use ::db::*;
+
+pub fn day(day: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd(2030, 1, day).and_hms(12, 0, 0)
+}
+
+pub fn book(token: String,
+            hotel_id: i32,
+            room_number: i32,
+            arrival: NaiveDateTime,
+            departure: NaiveDateTime)
+            -> (StatusCode, String) {
+    let mut res = hyper::Client::new()
+        .put("http://localhost:8080/api/booking/")
+        .body(&json::encode(&NewBooking {
+            ClientPersonID: None,
+            HotelID: Some(hotel_id),
+            RoomNumber: room_number,
+            ArrivalTime: arrival,
+            DepartureTime: departure,
+        }).unwrap())
+        .sign(token)
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    println!("body:    {:?}", resp_body);
+
+    (res.status, resp_body)
+}
+
+pub fn bookings(token: String) -> Vec<Booking> {
+    let mut res = hyper::Client::new()
+        .get("http://localhost:8080/api/account/bookings/")
+        .sign(token)
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    assert_eq!(res.status, StatusCode::Ok);
+
+    json::decode(&resp_body).unwrap()
+}
+
+pub fn cancel(token: String, booking_id: i32) -> (StatusCode, String) {
+    let mut res = hyper::Client::new()
+        .post(&format!("http://localhost:8080/api/booking/{}/cancel", booking_id))
+        .sign(token)
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    println!("body:    {:?}", resp_body);
+
+    (res.status, resp_body)
+}
+
+pub fn change(token: String, booking_id: i32, update: &UpdateBooking) -> (StatusCode, String) {
+    let mut res = hyper::Client::new()
+        .post(&format!("http://localhost:8080/api/booking/{}", booking_id))
+        .body(&json::encode(update).unwrap())
+        .sign(token)
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    println!("body:    {:?}", resp_body);
+
+    (res.status, resp_body)
+}
+
+fn transition(token: String, booking_id: i32, action: &str, body: &str) -> (StatusCode, String) {
+    let mut res = hyper::Client::new()
+        .post(&format!("http://localhost:8080/api/booking/{}/{}", booking_id, action))
+        .body(body)
+        .sign(token)
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    println!("body:    {:?}", resp_body);
+
+    (res.status, resp_body)
+}
+
+pub fn err_code(code: ErrorCode) -> String {
+    format!("\"err_code\":\"{}\"", code as i32)
+}
+
+#[test]
+fn double_booking() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+    create_room(owner_token, hotel.ID, 1);
+
+    let client_token = signup_random();
+
+    let (status, _) = book(client_token.clone(), hotel.ID, 1, day(1), day(3));
+    assert_eq!(status, StatusCode::Ok);
+
+    let (status, body) = book(signup_random(), hotel.ID, 1, day(2), day(4));
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::ConflictError)));
+    assert!(body.contains(&format!("Booked from {} to {}", day(1), day(3))));
+
+    // Departure day is free for the next guest
+    let (status, _) = book(client_token.clone(), hotel.ID, 1, day(3), day(5));
+    assert_eq!(status, StatusCode::Ok);
+
+    let (status, body) = book(client_token.clone(), hotel.ID, 1, day(10), day(8));
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::InvalidFieldsError)));
+
+    let (status, body) = book(client_token, hotel.ID, 2, day(10), day(12));
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::NotFoundError)));
+}
+
+#[test]
+fn concurrent_booking() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+    create_room(owner_token, hotel.ID, 1);
+
+    let hotel_id = hotel.ID;
+    let attempts = (0..8)
+        .map(|_| signup_random())
+        .map(|token| thread::spawn(move || book(token, hotel_id, 1, day(1), day(3)).0))
+        .collect::<Vec<_>>();
+
+    let booked = attempts.into_iter()
+        .map(|attempt| attempt.join().unwrap())
+        .filter(|status| *status == StatusCode::Ok)
+        .count();
+
+    assert_eq!(booked, 1);
+}
+
+#[test]
+fn cancellation() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+    create_room(owner_token.clone(), hotel.ID, 1);
+
+    // Every booking made in the ten years before 2030 is within the paid period
+    let conn = get_db_connection();
+    let ruleset_id: i32 = conn.query("INSERT INTO RuleSet (ManagerPersonID, Name, Body, \
+                                      IsDefault) VALUES (NULL, 'Strict', $1, false) RETURNING ID",
+               &[&"{\"RoomLevels\":[],\"ClientLevels\":[],\"RequireVerifiedEmail\":null,\
+                  \"Cancellation\":{\"FreeUntilDays\":3650,\"FeePercentage\":25}}"])
+        .unwrap()
+        .get(0)
+        .get(0);
+
+    conn.execute("UPDATE Hotel SET RuleSetID = $1 WHERE ID = $2", &[&ruleset_id, &hotel.ID])
+        .unwrap();
+
+    let client_login = random_str();
+    let client_pass = random_str();
+    signup_with(&client_login, &random_str(), &random_email(), &client_pass);
+    let (client_token, client_roles) = signin_with(&client_login, &client_pass);
+
+    let (status, _) = book(client_token.clone(), hotel.ID, 1, day(1), day(3));
+    assert_eq!(status, StatusCode::Ok);
+
+    let booking = bookings(client_token.clone()).pop().unwrap();
+    conn.execute("UPDATE Booking SET FullCost = 1000 WHERE ID = $1", &[&booking.ID])
+        .unwrap();
+
+    // Strangers can't cancel
+    let (status, _) = cancel(signup_random(), booking.ID);
+    assert_eq!(status, StatusCode::Forbidden);
+
+    let (status, body) = cancel(client_token.clone(), booking.ID);
+    assert_eq!(status, StatusCode::Ok);
+
+    let cancelled: Booking = json::decode(&body).unwrap();
+    assert!(cancelled.Cancelled);
+    assert_eq!(cancelled.CancelledBy, Some(client_roles.ID));
+    assert_eq!(cancelled.CancellationFee, 250);
+
+    let (status, body) = cancel(client_token.clone(), booking.ID);
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::ConflictError)));
+
+    // Cancelled booking frees the room
+    let (status, _) = book(client_token.clone(), hotel.ID, 1, day(1), day(3));
+    assert_eq!(status, StatusCode::Ok);
+
+    let booking = bookings(client_token)
+        .into_iter()
+        .filter(|booking| !booking.Cancelled)
+        .last()
+        .unwrap();
+
+    let login = random_str();
+    let passhash = random_str();
+    signup_with(&login, &random_str(), &random_email(), &passhash);
+    let (_, roles) = signin_with(&login, &passhash);
+    hire(owner_token, hotel.ID, roles.ID, StaffRole::Receptionist);
+    let (receptionist_token, _) = signin_with(&login, &passhash);
+
+    let (status, body) = cancel(receptionist_token, booking.ID);
+    assert_eq!(status, StatusCode::Ok);
+
+    let cancelled: Booking = json::decode(&body).unwrap();
+    assert_eq!(cancelled.CancelledBy, Some(roles.ID));
+}
+
+#[test]
+fn modification() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+    create_room(owner_token.clone(), hotel.ID, 1);
+    create_room(owner_token.clone(), hotel.ID, 2);
+
+    let client_token = signup_random();
+    let (status, _) = book(client_token.clone(), hotel.ID, 1, day(1), day(3));
+    assert_eq!(status, StatusCode::Ok);
+    let (status, _) = book(signup_random(), hotel.ID, 1, day(10), day(12));
+    assert_eq!(status, StatusCode::Ok);
+
+    let booking = bookings(client_token.clone()).pop().unwrap();
+
+    let (status, body) = change(client_token.clone(),
+                                booking.ID,
+                                &UpdateBooking {
+                                    RoomNumber: None,
+                                    ArrivalTime: None,
+                                    DepartureTime: Some(day(5)),
+                                });
+    assert_eq!(status, StatusCode::Ok);
+
+    let booking_change: BookingChange = json::decode(&body).unwrap();
+    assert_eq!(booking_change.OldDepartureTime, day(3));
+    assert_eq!(booking_change.NewDepartureTime, day(5));
+    assert_eq!(booking_change.NewArrivalTime, day(1));
+    assert_eq!(booking_change.PriceDifference,
+               booking_change.NewFullCost - booking_change.OldFullCost);
+
+    // Change must be stored, not only reported
+    let changed = bookings(client_token.clone()).pop().unwrap();
+    assert_eq!(changed.ID, booking.ID);
+    assert_eq!(changed.DepartureTime, day(5));
+    assert_eq!(changed.FullCost, booking_change.NewFullCost);
+
+    let history = get_db_connection()
+        .query(&BookingChange::select_builder()
+                   .filter("BookingID = $1")
+                   .build(),
+               &[&booking.ID])
+        .unwrap()
+        .into_iter()
+        .map(BookingChange::from)
+        .collect::<Vec<BookingChange>>();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].OldFullCost, booking.FullCost);
+    assert_eq!(history[0].PriceDifference, changed.FullCost - booking.FullCost);
+
+    // Nothing was paid yet, so the guest owes the new price
+    assert_eq!(payments(client_token.clone(), booking.ID).Balance, changed.FullCost);
+
+    // Only the other guest's booking is reported, not the one being changed
+    let (status, body) = change(client_token.clone(),
+                                booking.ID,
+                                &UpdateBooking {
+                                    RoomNumber: None,
+                                    ArrivalTime: Some(day(4)),
+                                    DepartureTime: Some(day(11)),
+                                });
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::ConflictError)));
+    assert!(body.contains(&format!("Booked from {} to {}", day(10), day(12))));
+    assert!(!body.contains(&format!("Booked from {} to {}", day(1), day(5))));
+
+    let (status, _) = change(signup_random(),
+                             booking.ID,
+                             &UpdateBooking {
+                                 RoomNumber: Some(2),
+                                 ArrivalTime: None,
+                                 DepartureTime: None,
+                             });
+    assert_eq!(status, StatusCode::Forbidden);
+
+    let login = random_str();
+    let passhash = random_str();
+    signup_with(&login, &random_str(), &random_email(), &passhash);
+    let (_, roles) = signin_with(&login, &passhash);
+    hire(owner_token, hotel.ID, roles.ID, StaffRole::Receptionist);
+    let (receptionist_token, _) = signin_with(&login, &passhash);
+
+    let (status, body) = change(receptionist_token,
+                                booking.ID,
+                                &UpdateBooking {
+                                    RoomNumber: Some(2),
+                                    ArrivalTime: None,
+                                    DepartureTime: None,
+                                });
+    assert_eq!(status, StatusCode::Ok);
+
+    let booking_change: BookingChange = json::decode(&body).unwrap();
+    assert_eq!(booking_change.OldRoomNumber, 1);
+    assert_eq!(booking_change.NewRoomNumber, 2);
+    assert_eq!(booking_change.ChangedBy, roles.ID);
+
+    let maintained = get_db_connection()
+        .query(&MaintainedBy::select_builder()
+                   .filter("BookingID = $1 and ReceptionistPersonID = $2")
+                   .build(),
+               &[&booking.ID, &roles.ID])
+        .unwrap();
+    assert_eq!(maintained.len(), 1);
+}
+
+#[test]
+fn staff_listing() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+    create_room(owner_token.clone(), hotel.ID, 1);
+    create_room(owner_token.clone(), hotel.ID, 2);
+
+    let client_login = random_str();
+    let client_pass = random_str();
+    signup_with(&client_login, &random_str(), &random_email(), &client_pass);
+    let (client_token, client_roles) = signin_with(&client_login, &client_pass);
+
+    assert_eq!(book(client_token.clone(), hotel.ID, 1, day(5), day(7)).0, StatusCode::Ok);
+    assert_eq!(book(client_token.clone(), hotel.ID, 2, day(1), day(3)).0, StatusCode::Ok);
+    assert_eq!(book(signup_random(), hotel.ID, 1, day(10), day(12)).0, StatusCode::Ok);
+
+    let login = random_str();
+    let passhash = random_str();
+    signup_with(&login, &random_str(), &random_email(), &passhash);
+    let (_, roles) = signin_with(&login, &passhash);
+    hire(owner_token, hotel.ID, roles.ID, StaffRole::Receptionist);
+    let (receptionist_token, _) = signin_with(&login, &passhash);
+
+    let list = |token: String, query: &str| {
+        let mut res = hyper::Client::new()
+            .get(&format!("http://localhost:8080/api/bookings/?{}", query))
+            .sign(token)
+            .send()
+            .unwrap();
+
+        let resp_body = response_body(&mut res);
+        println!("body:    {:?}", resp_body);
+
+        (res.status, resp_body)
+    };
+
+    let arrivals = |body: &str| {
+        json::decode::<Vec<Booking>>(body)
+            .unwrap()
+            .into_iter()
+            .map(|booking| booking.ArrivalTime)
+            .collect::<Vec<NaiveDateTime>>()
+    };
+
+    let (status, body) = list(receptionist_token.clone(),
+                              &format!("hotel={}&sort=arrival", hotel.ID));
+    assert_eq!(status, StatusCode::Ok);
+    assert_eq!(arrivals(&body), vec![day(1), day(5), day(10)]);
+
+    let (_, body) = list(receptionist_token.clone(),
+                         &format!("hotel={}&client={}&sort=-arrival", hotel.ID, client_roles.ID));
+    assert_eq!(arrivals(&body), vec![day(5), day(1)]);
+
+    let (_, body) = list(receptionist_token.clone(),
+                         &format!("hotel={}&room=1&date_from={}&date_to={}",
+                                  hotel.ID,
+                                  day(6).format("%Y-%m-%dT%H:%M:%S"),
+                                  day(11).format("%Y-%m-%dT%H:%M:%S")));
+    assert_eq!(arrivals(&body).len(), 2);
+
+    // Without hotel filter only hotels the receptionist works in are listed
+    let (_, body) = list(receptionist_token.clone(), "status=active&sort=arrival");
+    assert_eq!(arrivals(&body), vec![day(1), day(5), day(10)]);
+
+    let (_, body) = list(receptionist_token.clone(), "status=Cancelled");
+    assert!(arrivals(&body).is_empty());
+
+    let (status, body) = list(receptionist_token, "sort=price");
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::InvalidFieldsError)));
+
+    // Guests can't look at other guests' bookings
+    let (status, _) = list(client_token, &format!("hotel={}", hotel.ID));
+    assert_eq!(status, StatusCode::Forbidden);
+}
+
+#[test]
+fn api_key_listing() {
+    let owner_token = signin_owner();
+    let hotel_a = create_hotel(owner_token.clone());
+    let hotel_b = create_hotel(owner_token.clone());
+    create_room(owner_token.clone(), hotel_a.ID, 1);
+    create_room(owner_token.clone(), hotel_b.ID, 1);
+
+    assert_eq!(book(signup_random(), hotel_a.ID, 1, day(1), day(3)).0, StatusCode::Ok);
+    assert_eq!(book(signup_random(), hotel_b.ID, 1, day(1), day(3)).0, StatusCode::Ok);
+
+    let mut res = hyper::Client::new()
+        .put("http://localhost:8080/api/apikey/")
+        .body(&json::encode(&NewApiKey {
+                Name: "bookings-sync".to_owned(),
+                HotelIDs: vec![hotel_a.ID],
+                Scopes: vec![Scope::ReadBookings],
+            })
+            .unwrap())
+        .sign(owner_token)
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    assert_eq!(res.status, StatusCode::Ok);
+    let created: CreatedApiKey = json::decode(&resp_body).unwrap();
+
+    // Owner owns both hotels, but the key only sees the one it is bound to
+    let mut res = hyper::Client::new()
+        .get("http://localhost:8080/api/bookings/")
+        .header(Authorization(format!("Bearer {}", created.Key)))
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    assert_eq!(res.status, StatusCode::Ok);
+    let listed: Vec<Booking> = json::decode(&resp_body).unwrap();
+    assert!(listed.iter().any(|booking| booking.HotelID == hotel_a.ID));
+    assert!(listed.iter().all(|booking| booking.HotelID == hotel_a.ID));
+}
+
+#[test]
+fn nightly_pricing() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+    create_room(owner_token, hotel.ID, 1);
+
+    // Every client gets 15% off the 1000 per night of level 0 rooms
+    let conn = get_db_connection();
+    let ruleset_id: i32 = conn.query("INSERT INTO RuleSet (ManagerPersonID, Name, Body, \
+                                      IsDefault) VALUES (NULL, 'Discounted', $1, false) \
+                                      RETURNING ID",
+               &[&"{\"RoomLevels\":[{\"Name\":null,\"PerNight\":1000,\"Level\":0}],\
+                  \"ClientLevels\":[{\"Name\":null,\"Discount\":15,\"BookingsAmount\":0}],\
+                  \"RequireVerifiedEmail\":null,\"Cancellation\":null}"])
+        .unwrap()
+        .get(0)
+        .get(0);
+
+    conn.execute(&RoomLevel::insert_query(),
+                 &RoomLevel {
+                         Level: 0,
+                         RuleSetID: ruleset_id,
+                         LevelName: None,
+                         PerNight: 1000,
+                     }
+                     .insert_args())
+        .unwrap();
+    conn.execute(&ClientLevel::insert_query(),
+                 &ClientLevel {
+                         BookingsAmount: 0,
+                         RuleSetID: ruleset_id,
+                         LevelName: None,
+                         DiscountPercentage: 15,
+                     }
+                     .insert_args())
+        .unwrap();
+    conn.execute("UPDATE Hotel SET RuleSetID = $1 WHERE ID = $2", &[&ruleset_id, &hotel.ID])
+        .unwrap();
+
+    let client_token = signup_random();
+    let (status, _) = book(client_token.clone(), hotel.ID, 1, day(1), day(4));
+    assert_eq!(status, StatusCode::Ok);
+
+    let booking = bookings(client_token.clone()).pop().unwrap();
+    assert_eq!(booking.FullCost, 3 * 850);
+
+    let mut res = hyper::Client::new()
+        .get(&format!("http://localhost:8080/api/booking/{}", booking.ID))
+        .sign(client_token.clone())
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    assert_eq!(res.status, StatusCode::Ok);
+
+    let details: BookingDetails = json::decode(&resp_body).unwrap();
+    assert_eq!(details.Booking.ID, booking.ID);
+    assert_eq!(details.Nights
+                   .iter()
+                   .map(|night| night.Night)
+                   .collect::<Vec<NaiveDate>>(),
+               vec![day(1).date(), day(2).date(), day(3).date()]);
+    assert!(details.Nights
+        .iter()
+        .all(|night| night.BaseRate == 1000 && night.DiscountPercentage == 15 &&
+                     night.Price == 850));
+
+    // Shorter stay is repriced and breakdown follows it
+    let (status, body) = change(client_token,
+                                booking.ID,
+                                &UpdateBooking {
+                                    RoomNumber: None,
+                                    ArrivalTime: None,
+                                    DepartureTime: Some(day(2)),
+                                });
+    assert_eq!(status, StatusCode::Ok);
+
+    let booking_change: BookingChange = json::decode(&body).unwrap();
+    assert_eq!(booking_change.NewFullCost, 850);
+    assert_eq!(booking_change.PriceDifference, -2 * 850);
+
+    let nights = conn.query(&BookingNight::select_builder()
+                   .filter("BookingID = $1")
+                   .build(),
+               &[&booking.ID])
+        .unwrap();
+    assert_eq!(nights.len(), 1);
+}
+
+#[test]
+fn ruleset_update_reprices() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+    create_room(owner_token.clone(), hotel.ID, 1);
+
+    let conn = get_db_connection();
+    let ruleset_id: i32 = conn.query("INSERT INTO RuleSet (ManagerPersonID, Name, Body, \
+                                      IsDefault) VALUES (NULL, 'Seasonal', $1, false) \
+                                      RETURNING ID",
+               &[&"{\"RoomLevels\":[{\"Name\":null,\"PerNight\":1000,\"Level\":0}],\
+                  \"ClientLevels\":[{\"Name\":null,\"Discount\":0,\"BookingsAmount\":0}],\
+                  \"RequireVerifiedEmail\":null,\"Cancellation\":null}"])
+        .unwrap()
+        .get(0)
+        .get(0);
+    conn.execute("UPDATE Hotel SET RuleSetID = $1 WHERE ID = $2", &[&ruleset_id, &hotel.ID])
+        .unwrap();
+
+    let login = random_str();
+    let passhash = random_str();
+    signup_with(&login, &random_str(), &random_email(), &passhash);
+    let (_, roles) = signin_with(&login, &passhash);
+    hire(owner_token, hotel.ID, roles.ID, StaffRole::Manager);
+    let (manager_token, _) = signin_with(&login, &passhash);
+
+    // Repeated level is kept once instead of breaking the update
+    let res = hyper::Client::new()
+        .post(&format!("http://localhost:8080/api/manager/ruleset/{}", ruleset_id))
+        .body(&json::encode(&UpdateRuleSet {
+            Name: None,
+            Body: Some("{\"RoomLevels\":[{\"Name\":null,\"PerNight\":700,\"Level\":0},\
+                        {\"Name\":null,\"PerNight\":900,\"Level\":0}],\
+                        \"ClientLevels\":[{\"Name\":null,\"Discount\":0,\
+                        \"BookingsAmount\":0}],\"RequireVerifiedEmail\":null,\
+                        \"Cancellation\":null}"
+                .to_owned()),
+        }).unwrap())
+        .sign(manager_token)
+        .send()
+        .unwrap();
+    assert_eq!(res.status, StatusCode::Ok);
+
+    let levels = conn.query(&RoomLevel::select_builder()
+                   .filter("RuleSetID = $1")
+                   .build(),
+               &[&ruleset_id])
+        .unwrap()
+        .into_iter()
+        .map(RoomLevel::from)
+        .map(|level| level.PerNight)
+        .collect::<Vec<i32>>();
+    assert_eq!(levels, vec![700]);
+
+    let client_token = signup_random();
+    let (status, _) = book(client_token.clone(), hotel.ID, 1, day(1), day(3));
+    assert_eq!(status, StatusCode::Ok);
+    assert_eq!(bookings(client_token).pop().unwrap().FullCost, 2 * 700);
+}
+
+#[test]
+fn lifecycle() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+    create_room(owner_token.clone(), hotel.ID, 1);
+    create_room(owner_token.clone(), hotel.ID, 2);
+
+    let today = chrono::UTC::now().naive_local().date().and_hms(0, 0, 0);
+    let client_token = signup_random();
+    assert_eq!(book(client_token.clone(), hotel.ID, 1, today, today + Duration::days(2)).0,
+               StatusCode::Ok);
+    assert_eq!(book(client_token.clone(), hotel.ID, 2, day(1), day(3)).0,
+               StatusCode::Ok);
+
+    let mut current = bookings(client_token.clone());
+    current.sort_by_key(|booking| booking.ArrivalTime);
+    let (booking, future) = (current[0].clone(), current[1].clone());
+    assert_eq!(booking.Status, "Reserved");
+
+    let login = random_str();
+    let passhash = random_str();
+    signup_with(&login, &random_str(), &random_email(), &passhash);
+    let (_, roles) = signin_with(&login, &passhash);
+    hire(owner_token, hotel.ID, roles.ID, StaffRole::Receptionist);
+    let (receptionist_token, _) = signin_with(&login, &passhash);
+
+    // Guests don't check themselves in
+    let (status, _) = transition(client_token.clone(), booking.ID, "checkin", "");
+    assert_eq!(status, StatusCode::Forbidden);
+
+    let (status, body) = transition(receptionist_token.clone(), future.ID, "checkin", "");
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::ConflictError)));
+
+    let (status, body) = transition(receptionist_token.clone(), booking.ID, "checkin", "");
+    assert_eq!(status, StatusCode::Ok);
+    let checked_in: Booking = json::decode(&body).unwrap();
+    assert_eq!(checked_in.Status, "CheckedIn");
+    assert!(checked_in.CheckedInAt.is_some());
+
+    let maintained = get_db_connection()
+        .query(&MaintainedBy::select_builder()
+                   .filter("BookingID = $1 and ReceptionistPersonID = $2")
+                   .build(),
+               &[&booking.ID, &roles.ID])
+        .unwrap();
+    assert_eq!(maintained.len(), 1);
+
+    let (status, body) = cancel(client_token.clone(), booking.ID);
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::ConflictError)));
+
+    let (status, body) = transition(receptionist_token.clone(),
+                                    booking.ID,
+                                    "status",
+                                    "{\"Status\":\"NoShow\"}");
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::ConflictError)));
+
+    let (status, body) = transition(receptionist_token.clone(),
+                                    booking.ID,
+                                    "status",
+                                    "{\"Status\":\"Cancelled\"}");
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::InvalidFieldsError)));
+
+    let (status, _) = transition(receptionist_token.clone(), booking.ID, "checkout", "");
+    assert_eq!(status, StatusCode::Ok);
+
+    let (status, body) = transition(receptionist_token.clone(), booking.ID, "checkout", "");
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::ConflictError)));
+
+    let (status, _) = transition(receptionist_token.clone(),
+                                 future.ID,
+                                 "status",
+                                 "{\"Status\":\"Confirmed\"}");
+    assert_eq!(status, StatusCode::Ok);
+
+    // Nobody is late for a stay that hasn't started
+    let (status, body) = transition(receptionist_token,
+                                    future.ID,
+                                    "status",
+                                    "{\"Status\":\"NoShow\"}");
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::ConflictError)));
+
+    let statuses = bookings(client_token)
+        .into_iter()
+        .map(|booking| (booking.ID, booking.Status))
+        .collect::<Vec<(i32, String)>>();
+    assert!(statuses.contains(&(booking.ID, "CheckedOut".to_owned())));
+    assert!(statuses.contains(&(future.ID, "Confirmed".to_owned())));
+}
@@ -4,14 +4,34 @@ use rustc_serialize::json;
 
 use super::response_body;
 use super::random_str;
+use super::authorization::*;
 use ::db::schema::*;
 
 #[test]
 fn city() {
     let client = hyper::Client::new();
     let city_name = random_str();
+
+    // Only administrators manage cities
+    let res = client.put("http://localhost:8080/api/city/")
+        .body(&format!("{{ \"Name\":\"{}\" }}", city_name))
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+
+    let res = client.put("http://localhost:8080/api/city/")
+        .body(&format!("{{ \"Name\":\"{}\" }}", city_name))
+        .sign(signin_owner())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+
+    let admin_token = signin_admin();
     let mut res = client.put("http://localhost:8080/api/city/")
         .body(&format!("{{ \"Name\":\"{}\" }}", city_name))
+        .sign(admin_token.clone())
         .send()
         .unwrap();
 
@@ -22,6 +42,21 @@ fn city() {
     assert_eq!(resp_body, "");
     assert_eq!(res.status, StatusCode::Ok);
 
+    let city = cities().into_iter().find(|city| city.Name == city_name).unwrap();
+
+    let new_name = random_str();
+    let res = client.post(&format!("http://localhost:8080/api/city/{}", city.ID))
+        .body(&format!("{{ \"Name\":\"{}\" }}", new_name))
+        .sign(admin_token)
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+    assert_eq!(cities().into_iter().filter(|city| city.Name == new_name).count(), 1);
+}
+
+fn cities() -> Vec<City> {
+    let client = hyper::Client::new();
     let mut res = client.get("http://localhost:8080/api/city/")
         .send()
         .unwrap();
@@ -30,8 +65,7 @@ fn city() {
     println!("body:    {:?}", resp_body);
     println!("headers: {:?}", res.headers);
 
-    let cities: Vec<City> = json::decode(&resp_body).unwrap();
-
     assert_eq!(res.status, StatusCode::Ok);
-    assert_eq!(cities.into_iter().filter(|city| city.Name == city_name).count(), 1);
-}
\ No newline at end of file
+
+    json::decode(&resp_body).unwrap()
+}
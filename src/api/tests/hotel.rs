@@ -6,6 +6,7 @@ use rustc_serialize::json;
 use rand;
 
 use super::random_str;
+use super::random_email;
 use super::response_body;
 use super::authorization::*;
 use ::api::ruleset::DEFAULT_RULESET_ID;
@@ -220,4 +221,247 @@ fn update_hotel() {
     assert_eq!(hotel.Stars, Some(3));
 }
 
+#[test]
+fn employees() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+
+    let login = random_str();
+    let passhash = random_str();
+    signup_with(&login, &random_str(), &random_email(), &passhash);
+    let (emp_token, emp_roles) = signin_with(&login, &passhash);
+
+    // Only owner can hire
+    let client = hyper::Client::new();
+    let res = client.put(&format!("http://localhost:8080/api/hotel/{}/employee/", hotel.ID))
+        .body(&json::encode(&NewEmployee {
+            PersonID: emp_roles.ID,
+            Role: StaffRole::Receptionist,
+        }).unwrap())
+        .sign(emp_token)
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Forbidden);
+
+    let res = client.put(&format!("http://localhost:8080/api/hotel/{}/employee/", hotel.ID))
+        .body(&json::encode(&NewEmployee {
+            PersonID: emp_roles.ID,
+            Role: StaffRole::Receptionist,
+        }).unwrap())
+        .sign(owner_token.clone())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+    assert_eq!(employees_with_role(owner_token.clone(), hotel.ID, "Receptionist"),
+               vec![emp_roles.ID]);
+
+    let res = client.post(&format!("http://localhost:8080/api/hotel/{}/employee/{}",
+                                   hotel.ID,
+                                   emp_roles.ID))
+        .body(&json::encode(&UpdateEmployee { Role: StaffRole::Manager }).unwrap())
+        .sign(owner_token.clone())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+    assert!(employees_with_role(owner_token.clone(), hotel.ID, "Receptionist").is_empty());
+    assert_eq!(employees_with_role(owner_token.clone(), hotel.ID, "Manager"),
+               vec![emp_roles.ID]);
+
+    let res = client.delete(&format!("http://localhost:8080/api/hotel/{}/employee/{}",
+                                     hotel.ID,
+                                     emp_roles.ID))
+        .sign(owner_token.clone())
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+    assert!(employees_with_role(owner_token, hotel.ID, "Manager").is_empty());
+}
+
+#[test]
+fn employee_listing() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+
+    let hired = [StaffRole::Manager, StaffRole::Cleaner]
+        .iter()
+        .map(|role| {
+            let login = random_str();
+            let passhash = random_str();
+            signup_with(&login, &random_str(), &random_email(), &passhash);
+            let (_, roles) = signin_with(&login, &passhash);
+            hire(owner_token.clone(), hotel.ID, roles.ID, *role);
+            (roles.ID, *role)
+        })
+        .collect::<Vec<(i32, StaffRole)>>();
+
+    let mut res = hyper::Client::new()
+        .get(&format!("http://localhost:8080/api/hotel/{}/employees/", hotel.ID))
+        .sign(owner_token)
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    assert_eq!(res.status, StatusCode::Ok);
+
+    let mut listed = json::decode::<Vec<Employee>>(&resp_body)
+        .unwrap()
+        .into_iter()
+        .map(|emp| (emp.Person.ID, emp.Role))
+        .collect::<Vec<(i32, StaffRole)>>();
+    listed.sort_by_key(|&(id, _)| id);
+
+    assert_eq!(listed, hired);
+}
+
+#[test]
+fn scoped_roles() {
+    let owner_token = signin_owner();
+    let hotel_a = create_hotel(owner_token.clone());
+    let hotel_b = create_hotel(owner_token.clone());
+
+    let login = random_str();
+    let passhash = random_str();
+    signup_with(&login, &random_str(), &random_email(), &passhash);
+    let (_, emp_roles) = signin_with(&login, &passhash);
+
+    hire(owner_token.clone(), hotel_a.ID, emp_roles.ID, StaffRole::Manager);
+    hire(owner_token.clone(), hotel_b.ID, emp_roles.ID, StaffRole::Receptionist);
+
+    let (emp_token, emp_roles) = signin_with(&login, &passhash);
+    assert_eq!(emp_roles.HotelRoles.get(&hotel_a.ID), Some(&StaffRole::Manager));
+    assert_eq!(emp_roles.HotelRoles.get(&hotel_b.ID), Some(&StaffRole::Receptionist));
+
+    // Manager of hotel A is just a receptionist in hotel B
+    let client = hyper::Client::new();
+    for &(hotel_id, status) in &[(hotel_a.ID, StatusCode::Ok), (hotel_b.ID, StatusCode::Forbidden)] {
+        let res = client.put(&format!("http://localhost:8080/api/hotel/{}/room/", hotel_id))
+            .body(&json::encode(&NewRoom {
+                RoomNumber: 1,
+                RoomLevel: 0,
+                PhotoSetID: None,
+            }).unwrap())
+            .sign(emp_token.clone())
+            .send()
+            .unwrap();
+
+        assert_eq!(res.status, status);
+    }
+}
+
+#[test]
+fn roles_cache_invalidation() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+
+    let login = random_str();
+    let passhash = random_str();
+    signup_with(&login, &random_str(), &random_email(), &passhash);
+    let (emp_token, emp_roles) = signin_with(&login, &passhash);
+
+    let employees_status = |token: String| {
+        hyper::Client::new()
+            .get(&format!("http://localhost:8080/api/hotel/{}/employees/", hotel.ID))
+            .sign(token)
+            .send()
+            .unwrap()
+            .status
+    };
+
+    assert_eq!(employees_status(emp_token.clone()), StatusCode::Forbidden);
+
+    // Hiring must take effect without signing in again
+    hire(owner_token.clone(), hotel.ID, emp_roles.ID, StaffRole::Cleaner);
+    assert_eq!(employees_status(emp_token.clone()), StatusCode::Ok);
+
+    let res = hyper::Client::new()
+        .delete(&format!("http://localhost:8080/api/hotel/{}/employee/{}",
+                         hotel.ID,
+                         emp_roles.ID))
+        .sign(owner_token)
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+    assert_eq!(employees_status(emp_token), StatusCode::Forbidden);
+}
+
+pub fn hire(owner_token: String, hotel_id: i32, person_id: i32, role: StaffRole) {
+    let client = hyper::Client::new();
+    let res = client.put(&format!("http://localhost:8080/api/hotel/{}/employee/", hotel_id))
+        .body(&json::encode(&NewEmployee {
+            PersonID: person_id,
+            Role: role,
+        }).unwrap())
+        .sign(owner_token)
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+}
+
+fn employees_with_role(token: String, hotel_id: i32, role: &str) -> Vec<i32> {
+    let client = hyper::Client::new();
+    let mut res = client.get(&format!("http://localhost:8080/api/hotel/{}/employees/?role={}",
+                                      hotel_id,
+                                      role))
+        .sign(token)
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    println!("body:    {:?}", resp_body);
+    assert_eq!(res.status, StatusCode::Ok);
+
+    let employees: Vec<Employee> = json::decode(&resp_body).unwrap();
+    employees.into_iter().map(|emp| emp.Person.ID).collect()
+}
+
+pub fn create_room(token: String, hotel_id: i32, room_number: i32) {
+    let client = hyper::Client::new();
+    let res = client.put(&format!("http://localhost:8080/api/hotel/{}/room/", hotel_id))
+        .body(&json::encode(&NewRoom {
+            RoomNumber: room_number,
+            RoomLevel: 0,
+            PhotoSetID: None,
+        }).unwrap())
+        .sign(token)
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+}
+
+pub fn create_hotel(token: String) -> Hotel {
+    let name = random_str();
+    let desc = random_str();
+
+    let client = hyper::Client::new();
+    let res = client.put("http://localhost:8080/api/hotel/")
+        .body(&json::encode(&NewHotel {
+            CityID: 3,
+            Name: name.clone(),
+            Description: desc.clone(),
+            Stars: Some(5)
+        }).unwrap())
+        .sign(token)
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status, StatusCode::Ok);
+
+    let mut res = client.get("http://localhost:8080/api/hotels/").send().unwrap();
+    let resp_body = response_body(&mut res);
+    assert_eq!(res.status, StatusCode::Ok);
+
+    let hotels: Vec<Hotel> = json::decode(&resp_body).unwrap();
+    hotels.into_iter()
+        .filter(|hotel| hotel.Name == name && hotel.Description == desc)
+        .last()
+        .expect("No putted hotel in get result")
+}
+
 // TODO Test all of hotels api
\ No newline at end of file
@@ -0,0 +1,69 @@
+use hyper;
+use hyper::status::StatusCode;
+
+use super::response_body;
+use super::authorization::*;
+use super::booking::{book, bookings, day, err_code};
+use super::hotel::{create_hotel, create_room};
+use ::proto::error::ErrorCode;
+
+fn invoice(token: String, booking_id: i32, format: &str) -> (StatusCode, String, String) {
+    let mut res = hyper::Client::new()
+        .get(&format!("http://localhost:8080/api/booking/{}/invoice?format={}",
+                      booking_id,
+                      format))
+        .sign(token)
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    println!("body:    {:?}", resp_body);
+
+    let content_type = res.headers
+        .get_raw("Content-Type")
+        .map(|value| String::from_utf8(value[0].clone()).unwrap())
+        .unwrap_or(String::new());
+
+    (res.status, content_type, resp_body)
+}
+
+#[test]
+fn numbering_and_formats() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+    create_room(owner_token.clone(), hotel.ID, 1);
+
+    let client_token = signup_random();
+    assert_eq!(book(client_token.clone(), hotel.ID, 1, day(1), day(3)).0, StatusCode::Ok);
+    assert_eq!(book(client_token.clone(), hotel.ID, 1, day(5), day(6)).0, StatusCode::Ok);
+
+    let mut ids = bookings(client_token.clone())
+        .into_iter()
+        .map(|booking| booking.ID)
+        .collect::<Vec<i32>>();
+    ids.sort();
+
+    let (status, content_type, body) = invoice(client_token.clone(), ids[0], "html");
+    assert_eq!(status, StatusCode::Ok);
+    assert!(content_type.starts_with("text/html"));
+    assert!(body.contains(&format!("Invoice {}-000001", hotel.ID)));
+    assert!(body.contains("Night of 2030-01-02"));
+
+    // Staff printing it again doesn't take a new number
+    let (status, _, body) = invoice(owner_token.clone(), ids[0], "html");
+    assert_eq!(status, StatusCode::Ok);
+    assert!(body.contains(&format!("Invoice {}-000001", hotel.ID)));
+
+    let (status, content_type, body) = invoice(client_token.clone(), ids[1], "pdf");
+    assert_eq!(status, StatusCode::Ok);
+    assert_eq!(content_type, "application/pdf");
+    assert!(body.starts_with("%PDF-"));
+    assert!(body.contains(&format!("Invoice {}-000002", hotel.ID)));
+
+    let (status, _, body) = invoice(client_token, ids[1], "doc");
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::InvalidFieldsError)));
+
+    let (status, _, _) = invoice(signup_random(), ids[1], "html");
+    assert_eq!(status, StatusCode::Forbidden);
+}
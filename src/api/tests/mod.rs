@@ -1,8 +1,13 @@
 mod account;
+mod admin;
+mod apikey;
 mod authorization;
 mod booking;
 mod city;
 mod hotel;
+mod invoice;
+mod payment;
+mod reservation;
 
 use hyper::client::response::Response;
 use std::io::Read;
@@ -27,3 +32,6 @@ fn random_str() -> String {
         .collect()
 }
 
+fn random_email() -> String {
+    format!("{}@example.com", random_str())
+}
@@ -0,0 +1,257 @@
+use hyper;
+use hyper::status::StatusCode;
+use rustc_serialize::json;
+
+use super::response_body;
+use super::random_str;
+use super::random_email;
+use super::authorization::*;
+use super::booking::{book, bookings, change, day, err_code};
+use super::hotel::{create_hotel, create_room, hire};
+use ::proto::error::ErrorCode;
+use ::proto::schema::*;
+
+pub fn payments(token: String, booking_id: i32) -> BookingLedger {
+    let mut res = hyper::Client::new()
+        .get(&format!("http://localhost:8080/api/booking/{}/payments", booking_id))
+        .sign(token)
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    println!("body:    {:?}", resp_body);
+    assert_eq!(res.status, StatusCode::Ok);
+
+    json::decode(&resp_body).unwrap()
+}
+
+fn record(token: String, booking_id: i32, payment: &NewPayment) -> (StatusCode, String) {
+    let mut res = hyper::Client::new()
+        .post(&format!("http://localhost:8080/api/booking/{}/payments", booking_id))
+        .body(&json::encode(payment).unwrap())
+        .sign(token)
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    println!("body:    {:?}", resp_body);
+
+    (res.status, resp_body)
+}
+
+fn pay(token: String, booking_id: i32, card_token: &str) -> (StatusCode, String) {
+    let mut res = hyper::Client::new()
+        .post(&format!("http://localhost:8080/api/booking/{}/pay", booking_id))
+        .body(&json::encode(&OnlinePayment { CardToken: card_token.to_owned() }).unwrap())
+        .sign(token)
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    println!("body:    {:?}", resp_body);
+
+    (res.status, resp_body)
+}
+
+#[test]
+fn ledger() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+    create_room(owner_token.clone(), hotel.ID, 1);
+
+    let client_token = signup_random();
+    let (status, _) = book(client_token.clone(), hotel.ID, 1, day(1), day(3));
+    assert_eq!(status, StatusCode::Ok);
+
+    let booking = bookings(client_token.clone()).pop().unwrap();
+    assert!(!booking.Paid);
+
+    let ledger = payments(client_token.clone(), booking.ID);
+    assert_eq!(ledger.Entries.len(), 1);
+    assert_eq!(ledger.Entries[0].Kind, "Charge");
+    assert_eq!(ledger.Entries[0].Amount, booking.FullCost);
+    assert_eq!(ledger.Balance, booking.FullCost);
+
+    let login = random_str();
+    let passhash = random_str();
+    signup_with(&login, &random_str(), &random_email(), &passhash);
+    let (_, roles) = signin_with(&login, &passhash);
+    hire(owner_token, hotel.ID, roles.ID, StaffRole::Receptionist);
+    let (receptionist_token, _) = signin_with(&login, &passhash);
+
+    let deposit = NewPayment {
+        Kind: LedgerKind::Deposit,
+        Method: PaymentMethod::Cash,
+        Amount: 50,
+        Reference: None,
+    };
+
+    // Guests can't write the ledger themselves
+    let (status, _) = record(client_token.clone(), booking.ID, &deposit);
+    assert_eq!(status, StatusCode::Forbidden);
+
+    let (status, body) = record(receptionist_token.clone(), booking.ID, &deposit);
+    assert_eq!(status, StatusCode::Ok);
+    let ledger: BookingLedger = json::decode(&body).unwrap();
+    assert_eq!(ledger.Balance, booking.FullCost - 50);
+    assert_eq!(ledger.Entries.last().unwrap().RecordedBy, Some(roles.ID));
+
+    let (status, body) = record(receptionist_token.clone(),
+                                booking.ID,
+                                &NewPayment {
+                                    Kind: LedgerKind::Charge,
+                                    Method: PaymentMethod::Online,
+                                    Amount: 0,
+                                    Reference: None,
+                                });
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::InvalidFieldsError)));
+    assert!(body.contains("\"field\":\"Kind\""));
+    assert!(body.contains("\"field\":\"Method\""));
+    assert!(body.contains("\"field\":\"Amount\""));
+
+    let (status, body) = pay(client_token.clone(), booking.ID, "tok_declined");
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::PaymentDeclinedError)));
+    assert_eq!(payments(client_token.clone(), booking.ID).Balance, booking.FullCost - 50);
+
+    let (status, body) = pay(client_token.clone(), booking.ID, "tok_visa");
+    assert_eq!(status, StatusCode::Ok);
+    let ledger: BookingLedger = json::decode(&body).unwrap();
+    assert_eq!(ledger.Balance, 0);
+    assert!(bookings(client_token.clone()).pop().unwrap().Paid);
+
+    let (status, body) = pay(client_token.clone(), booking.ID, "tok_visa");
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::ConflictError)));
+
+    let online_reference = ledger.Entries.last().unwrap().Reference.clone();
+
+    let (status, body) = record(receptionist_token.clone(),
+                                booking.ID,
+                                &NewPayment {
+                                    Kind: LedgerKind::Refund,
+                                    Method: PaymentMethod::Online,
+                                    Amount: 30,
+                                    Reference: online_reference,
+                                });
+    assert_eq!(status, StatusCode::Ok);
+    let ledger: BookingLedger = json::decode(&body).unwrap();
+    assert_eq!(ledger.Balance, 30);
+    assert!(!bookings(client_token).pop().unwrap().Paid);
+
+    let (status, body) = record(receptionist_token,
+                                booking.ID,
+                                &NewPayment {
+                                    Kind: LedgerKind::Refund,
+                                    Method: PaymentMethod::Cash,
+                                    Amount: booking.FullCost,
+                                    Reference: None,
+                                });
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::InvalidFieldsError)));
+}
+
+#[test]
+fn repricing() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+    create_room(owner_token, hotel.ID, 1);
+
+    let client_token = signup_random();
+    let (status, _) = book(client_token.clone(), hotel.ID, 1, day(1), day(3));
+    assert_eq!(status, StatusCode::Ok);
+
+    let booking = bookings(client_token.clone()).pop().unwrap();
+    let (status, _) = pay(client_token.clone(), booking.ID, "tok_visa");
+    assert_eq!(status, StatusCode::Ok);
+    assert!(bookings(client_token.clone()).pop().unwrap().Paid);
+
+    // Longer stay is charged on top of what was already paid
+    let (status, _) = change(client_token.clone(),
+                             booking.ID,
+                             &UpdateBooking {
+                                 RoomNumber: None,
+                                 ArrivalTime: None,
+                                 DepartureTime: Some(day(5)),
+                             });
+    assert_eq!(status, StatusCode::Ok);
+
+    let extended = bookings(client_token.clone()).pop().unwrap();
+    assert!(extended.FullCost > booking.FullCost);
+    assert!(!extended.Paid);
+
+    let ledger = payments(client_token, booking.ID);
+    assert_eq!(ledger.Balance, extended.FullCost - booking.FullCost);
+
+    let charge = ledger.Entries.last().unwrap();
+    assert_eq!(charge.Kind, "Charge");
+    assert_eq!(charge.Amount, extended.FullCost - booking.FullCost);
+}
+
+#[test]
+fn online_refund_bound() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+    create_room(owner_token.clone(), hotel.ID, 1);
+
+    let client_token = signup_random();
+    let (status, _) = book(client_token.clone(), hotel.ID, 1, day(1), day(3));
+    assert_eq!(status, StatusCode::Ok);
+    let booking = bookings(client_token.clone()).pop().unwrap();
+
+    let login = random_str();
+    let passhash = random_str();
+    signup_with(&login, &random_str(), &random_email(), &passhash);
+    let (_, roles) = signin_with(&login, &passhash);
+    hire(owner_token, hotel.ID, roles.ID, StaffRole::Receptionist);
+    let (receptionist_token, _) = signin_with(&login, &passhash);
+
+    let (status, _) = record(receptionist_token.clone(),
+                             booking.ID,
+                             &NewPayment {
+                                 Kind: LedgerKind::Deposit,
+                                 Method: PaymentMethod::Cash,
+                                 Amount: 50,
+                                 Reference: None,
+                             });
+    assert_eq!(status, StatusCode::Ok);
+
+    let (status, body) = pay(client_token.clone(), booking.ID, "tok_visa");
+    assert_eq!(status, StatusCode::Ok);
+    let ledger: BookingLedger = json::decode(&body).unwrap();
+    let online = ledger.Entries.last().unwrap();
+    let online_amount = booking.FullCost - 50;
+    assert_eq!(online.Amount, online_amount);
+
+    let online_refund = |amount: i32| {
+        record(receptionist_token.clone(),
+               booking.ID,
+               &NewPayment {
+                   Kind: LedgerKind::Refund,
+                   Method: PaymentMethod::Online,
+                   Amount: amount,
+                   Reference: online.Reference.clone(),
+               })
+    };
+
+    // Cash deposit is within what the booking received, but not on the card
+    let (status, body) = online_refund(online_amount + 1);
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::InvalidFieldsError)));
+    assert!(body.contains("\"field\":\"Amount\""));
+
+    let (status, body) = online_refund(online_amount - 10);
+    assert_eq!(status, StatusCode::Ok);
+    let ledger: BookingLedger = json::decode(&body).unwrap();
+    assert_eq!(ledger.Entries.last().unwrap().RefundOf, Some(online.ID));
+
+    // Earlier refunds against the same payment count too
+    let (status, body) = online_refund(11);
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains("\"field\":\"Amount\""));
+
+    let (status, _) = online_refund(10);
+    assert_eq!(status, StatusCode::Ok);
+    assert_eq!(payments(client_token, booking.ID).Balance, booking.FullCost - 50);
+}
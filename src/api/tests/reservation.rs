@@ -0,0 +1,128 @@
+use hyper;
+use hyper::status::StatusCode;
+use rustc_serialize::json;
+use chrono::NaiveDateTime;
+
+use super::response_body;
+use super::authorization::*;
+use super::booking::{book, cancel, day, err_code};
+use super::hotel::{create_hotel, create_room};
+use ::proto::error::ErrorCode;
+use ::proto::schema::*;
+use ::db::schema::*;
+use ::db::*;
+
+fn room(room_number: i32, arrival: NaiveDateTime, departure: NaiveDateTime) -> ReservationRoom {
+    ReservationRoom {
+        RoomNumber: room_number,
+        ArrivalTime: arrival,
+        DepartureTime: departure,
+    }
+}
+
+fn reserve(token: String, hotel_id: i32, rooms: Vec<ReservationRoom>) -> (StatusCode, String) {
+    let mut res = hyper::Client::new()
+        .put("http://localhost:8080/api/reservation/")
+        .body(&json::encode(&NewReservation {
+            ClientPersonID: None,
+            HotelID: hotel_id,
+            Rooms: rooms,
+        }).unwrap())
+        .sign(token)
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    println!("body:    {:?}", resp_body);
+
+    (res.status, resp_body)
+}
+
+fn reservation(token: String, reservation_id: i32) -> (StatusCode, String) {
+    let mut res = hyper::Client::new()
+        .get(&format!("http://localhost:8080/api/reservation/{}", reservation_id))
+        .sign(token)
+        .send()
+        .unwrap();
+
+    let resp_body = response_body(&mut res);
+    println!("body:    {:?}", resp_body);
+
+    (res.status, resp_body)
+}
+
+#[test]
+fn group_reservation() {
+    let owner_token = signin_owner();
+    let hotel = create_hotel(owner_token.clone());
+    create_room(owner_token.clone(), hotel.ID, 1);
+    create_room(owner_token, hotel.ID, 2);
+
+    // Suite costs more than the rooms above
+    get_db_connection()
+        .execute(&Room::insert_query(),
+                 &Room {
+                         HotelID: hotel.ID,
+                         RoomNumber: 3,
+                         RoomLevel: 3,
+                         PhotoSetID: None,
+                     }
+                     .insert_args())
+        .unwrap();
+
+    assert_eq!(book(signup_random(), hotel.ID, 2, day(5), day(7)).0, StatusCode::Ok);
+
+    let client_token = signup_random();
+    let (status, body) = reserve(client_token.clone(),
+                                 hotel.ID,
+                                 vec![room(1, day(1), day(3)),
+                                      room(2, day(1), day(3)),
+                                      room(3, day(1), day(3))]);
+    assert_eq!(status, StatusCode::Ok);
+
+    let details: ReservationDetails = json::decode(&body).unwrap();
+    assert_eq!(details.Bookings.len(), 3);
+    assert!(details.Bookings
+        .iter()
+        .all(|booking| booking.ReservationID == Some(details.Reservation.ID)));
+    assert!(details.Bookings[2].FullCost > details.Bookings[0].FullCost);
+    assert_eq!(details.FullCost,
+               details.Bookings.iter().fold(0, |cost, booking| cost + booking.FullCost));
+
+    // Room 2 is taken, so room 1 must not be booked either
+    let (status, body) = reserve(client_token.clone(),
+                                 hotel.ID,
+                                 vec![room(1, day(10), day(12)), room(2, day(6), day(8))]);
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::ConflictError)));
+    assert!(body.contains(&format!("Booked from {} to {}", day(5), day(7))));
+
+    let (status, _) = reserve(client_token.clone(), hotel.ID, vec![room(1, day(10), day(12))]);
+    assert_eq!(status, StatusCode::Ok);
+
+    let (status, body) = reserve(client_token.clone(),
+                                 hotel.ID,
+                                 vec![room(1, day(20), day(22)), room(1, day(21), day(23))]);
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::InvalidFieldsError)));
+
+    let (status, body) = reserve(client_token.clone(), hotel.ID, vec![]);
+    assert_eq!(status, StatusCode::Forbidden);
+    assert!(body.contains(&err_code(ErrorCode::InvalidFieldsError)));
+
+    // Rooms are cancelled one at a time
+    let suite = details.Bookings[2].clone();
+    let (status, _) = cancel(client_token.clone(), suite.ID);
+    assert_eq!(status, StatusCode::Ok);
+
+    let (status, body) = reservation(client_token, details.Reservation.ID);
+    assert_eq!(status, StatusCode::Ok);
+
+    let after_cancel: ReservationDetails = json::decode(&body).unwrap();
+    assert!(after_cancel.Bookings[2].Cancelled);
+    assert!(!after_cancel.Bookings[0].Cancelled);
+    assert_eq!(after_cancel.FullCost, details.FullCost - suite.FullCost);
+
+    let (status, _) = reservation(signup_random(), details.Reservation.ID);
+    assert_eq!(status, StatusCode::Forbidden);
+}
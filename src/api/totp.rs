@@ -0,0 +1,197 @@
+use std::ascii::AsciiExt;
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
+use crypto::sha1::Sha1;
+use crypto::util::fixed_time_eq;
+use rand::{OsRng, Rng};
+
+// RFC 6238 defaults, the only ones authenticator apps reliably support
+pub const DIGITS: u32 = 6;
+pub const STEP_SECS: i64 = 30;
+const SECRET_LEN: usize = 20;
+
+// Codes from adjacent steps are accepted to tolerate clock drift
+const DRIFT_STEPS: i64 = 1;
+
+const BASE32_ALPHABET: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; SECRET_LEN];
+    OsRng::new()
+        .expect("Failed to open OS random number generator")
+        .fill_bytes(&mut secret);
+
+    secret
+}
+
+// RFC 4226 HOTP with dynamic truncation
+pub fn hotp(key: &[u8], counter: u64, digits: u32) -> u32 {
+    let mut counter_bytes = [0u8; 8];
+    for i in 0..8 {
+        counter_bytes[7 - i] = (counter >> (8 * i)) as u8;
+    }
+
+    let mut mac = Hmac::new(Sha1::new(), key);
+    mac.input(&counter_bytes);
+    let result = mac.result();
+    let hash = result.code();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = ((hash[offset] as u32 & 0x7f) << 24) | ((hash[offset + 1] as u32) << 16) |
+                 ((hash[offset + 2] as u32) << 8) | (hash[offset + 3] as u32);
+
+    binary % 10u32.pow(digits)
+}
+
+pub fn step_at(unix_time: i64) -> i64 {
+    unix_time / STEP_SECS
+}
+
+pub fn code_at(key: &[u8], unix_time: i64) -> String {
+    format_code(hotp(key, step_at(unix_time) as u64, DIGITS))
+}
+
+// Returns step matched by the code, steps up to `last_step` are treated as used
+pub fn verify(key: &[u8], code: &str, unix_time: i64, last_step: Option<i64>) -> Option<i64> {
+    let current = step_at(unix_time);
+
+    ((current - DRIFT_STEPS)..(current + DRIFT_STEPS + 1))
+        .filter(|step| *step >= 0 && last_step.map_or(true, |last| *step > last))
+        .find(|step| {
+            let expected = format_code(hotp(key, *step as u64, DIGITS));
+            fixed_time_eq(expected.as_bytes(), code.trim().as_bytes())
+        })
+}
+
+fn format_code(code: u32) -> String {
+    format!("{:01$}", code, DIGITS as usize)
+}
+
+// Provisioning URI understood by authenticator apps, usually shown as QR code
+pub fn uri(issuer: &str, account: &str, secret: &[u8]) -> String {
+    format!("otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            percent_encode(issuer),
+            percent_encode(account),
+            base32_encode(secret),
+            percent_encode(issuer),
+            DIGITS,
+            STEP_SECS)
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+// RFC 4648 base32 without padding
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() * 8 + 4) / 5);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    out
+}
+
+pub fn base32_decode(s: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in s.trim_right_matches('=').bytes() {
+        let c = c.to_ascii_uppercase();
+        let value = match BASE32_ALPHABET.iter().position(|a| *a == c) {
+            Some(value) => value as u32,
+            None => return None,
+        };
+
+        buffer = (buffer << 5) | value;
+        bits += 5;
+
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RFC_SECRET: &'static [u8] = b"12345678901234567890";
+
+    #[test]
+    fn rfc4226_hotp() {
+        let expected = [755224, 287082, 359152, 969429, 338314, 254676, 287922, 162583, 399871,
+                        520489];
+
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(hotp(RFC_SECRET, counter as u64, 6), *code);
+        }
+    }
+
+    #[test]
+    fn rfc6238_totp() {
+        let vectors = [(59, 94287082),
+                       (1111111109, 7081804),
+                       (1111111111, 14050471),
+                       (1234567890, 89005924),
+                       (2000000000, 69279037),
+                       (20000000000, 65353130)];
+
+        for &(time, code) in &vectors {
+            assert_eq!(hotp(RFC_SECRET, step_at(time) as u64, 8), code);
+        }
+
+        assert_eq!(code_at(RFC_SECRET, 1111111109), "081804");
+    }
+
+    #[test]
+    fn verify_window() {
+        let code = code_at(RFC_SECRET, 1234567890);
+
+        assert_eq!(verify(RFC_SECRET, &code, 1234567890, None), Some(step_at(1234567890)));
+        assert!(verify(RFC_SECRET, &code, 1234567890 + STEP_SECS, None).is_some());
+        assert!(verify(RFC_SECRET, &code, 1234567890 + 3 * STEP_SECS, None).is_none());
+        assert!(verify(RFC_SECRET, "000000", 1234567890, None).is_none());
+    }
+
+    #[test]
+    fn verify_replay() {
+        let code = code_at(RFC_SECRET, 1234567890);
+        let step = verify(RFC_SECRET, &code, 1234567890, None);
+
+        assert!(verify(RFC_SECRET, &code, 1234567890, step).is_none());
+    }
+
+    #[test]
+    fn base32() {
+        assert_eq!(base32_encode(RFC_SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(base32_decode("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ").unwrap(), RFC_SECRET);
+        assert_eq!(base32_encode(b"f"), "MY");
+        assert_eq!(base32_decode("my======").unwrap(), b"f");
+        assert!(base32_decode("M1").is_none());
+    }
+}
@@ -0,0 +1,198 @@
+use hyper::status::StatusCode;
+use iron::prelude::*;
+use postgres::Connection;
+use chrono;
+
+use super::request_body;
+use ::api::authorization::{Authorizer, new_token};
+use ::api::password::token_digest;
+use ::api::totp;
+use ::proto::error::*;
+use ::proto::response::*;
+use ::proto::schema::*;
+use ::db::schema::*;
+use ::db::*;
+use ::config;
+
+const RECOVERY_CODES: usize = 10;
+const RECOVERY_CODE_LEN: usize = 10;
+
+fn find(conn: &Connection, person_id: i32) -> Option<TwoFactor> {
+    conn.query(&TwoFactor::select_builder()
+                   .filter("PersonID = $1")
+                   .build(),
+               &[&person_id])
+        .unwrap()
+        .into_iter()
+        .map(TwoFactor::from)
+        .last()
+}
+
+pub fn enabled(conn: &Connection, person_id: i32) -> bool {
+    find(conn, person_id).map_or(false, |two_factor| two_factor.Enabled)
+}
+
+// Accepts either a fresh TOTP code or an unused recovery code
+pub fn verify(conn: &Connection, person_id: i32, code: &str) -> bool {
+    let two_factor = match find(conn, person_id) {
+        Some(ref two_factor) if two_factor.Enabled => two_factor.clone(),
+        _ => return false,
+    };
+
+    let secret = totp::base32_decode(&two_factor.Secret).expect("Database is inconsistent");
+    let now = chrono::UTC::now().timestamp();
+
+    if let Some(step) = totp::verify(&secret, code, now, two_factor.LastStep) {
+        return claim_step(conn, person_id, step);
+    }
+
+    conn.execute(&RecoveryCode::update_builder()
+                     .set("Used")
+                     .filter("PersonID = $2 and CodeHash = $3 and Used = false")
+                     .build(),
+                 &[&true, &person_id, &token_digest(code.trim())])
+        .unwrap() != 0
+}
+
+// Conditional update, so concurrent requests can't both use the same code
+fn claim_step(conn: &Connection, person_id: i32, step: i64) -> bool {
+    conn.execute(&TwoFactor::update_builder()
+                     .set("LastStep")
+                     .filter("PersonID = $2 and (LastStep IS NULL or LastStep < $1)")
+                     .build(),
+                 &[&step, &person_id])
+        .unwrap() != 0
+}
+
+fn issue_recovery_codes(conn: &Connection, person_id: i32) -> Vec<String> {
+    conn.execute(&RecoveryCode::delete_builder()
+                     .filter("PersonID = $1")
+                     .build(),
+                 &[&person_id])
+        .unwrap();
+
+    (0..RECOVERY_CODES)
+        .map(|_| {
+            let code = new_token()[..RECOVERY_CODE_LEN].to_owned();
+
+            conn.execute(&RecoveryCode::insert_query(),
+                         &[&person_id, &token_digest(&code), &false])
+                .unwrap();
+
+            code
+        })
+        .collect()
+}
+
+// Starts enrollment, factor is not required until confirmed with a code
+pub fn enroll(req: &mut Request) -> IronResult<Response> {
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_session()?;
+
+    info!("request POST /account/2fa/ {{ id: {} }}", user.id);
+
+    if !user.roles.Owner && !user.roles.Manager {
+        return Err(NotAuthorizedError::from_str("Two-factor authentication is available to \
+                                                 owners and managers only")
+            .into());
+    }
+
+    if enabled(&conn, user.id) {
+        return Err(NotAuthorizedError::from_str("Two-factor authentication is already enabled")
+            .into());
+    }
+
+    let login: String = conn.query(&Person::select_builder()
+                       .columns("Login")
+                       .filter("ID = $1")
+                       .build(),
+                   &[&user.id])
+        .unwrap()
+        .get(0)
+        .get("Login");
+
+    let secret = totp::generate_secret();
+
+    conn.execute(&TwoFactor::delete_builder()
+                     .filter("PersonID = $1")
+                     .build(),
+                 &[&user.id])
+        .unwrap();
+
+    conn.execute(&TwoFactor::insert_query(),
+                 &TwoFactor {
+                         PersonID: user.id,
+                         Secret: totp::base32_encode(&secret),
+                         Enabled: false,
+                         Created: chrono::UTC::now().naive_local(),
+                         LastStep: None,
+                     }
+                     .insert_args())
+        .unwrap();
+
+    Ok(TwoFactorEnrollment {
+            Secret: totp::base32_encode(&secret),
+            Uri: totp::uri(&config::TWO_FACTOR_ISSUER, &login, &secret),
+        }
+        .as_response())
+}
+
+pub fn confirm(req: &mut Request) -> IronResult<Response> {
+    let code: TwoFactorCode = request_body(req)?;
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_session()?;
+
+    info!("request POST /account/2fa/confirm {{ id: {} }}", user.id);
+
+    let two_factor = match find(&conn, user.id) {
+        Some(ref two_factor) if !two_factor.Enabled => two_factor.clone(),
+        _ => {
+            return Err(NotFoundError::from_str("No pending two-factor enrollment").into());
+        }
+    };
+
+    let secret = totp::base32_decode(&two_factor.Secret).expect("Database is inconsistent");
+    let step = totp::verify(&secret, &code.Code, chrono::UTC::now().timestamp(), None)
+        .ok_or(box InvalidTokenError::from_str("Invalid two-factor code") as Box<ApiError>)?;
+
+    conn.execute(&TwoFactor::update_builder()
+                     .set("Enabled")
+                     .set("LastStep")
+                     .filter("PersonID = $3")
+                     .build(),
+                 &[&true, &step, &user.id])
+        .unwrap();
+
+    Ok(RecoveryCodes { RecoveryCodes: issue_recovery_codes(&conn, user.id) }.as_response())
+}
+
+pub fn disable(req: &mut Request) -> IronResult<Response> {
+    let disable: TwoFactorDisable = request_body(req)?;
+
+    let conn = get_db_connection();
+    let user = Authorizer::authorize_request(&conn, req)?;
+    user.require_session()?;
+
+    info!("request POST /account/2fa/disable {{ id: {} }}", user.id);
+
+    if !Authorizer::check_password(&conn, user.id, &disable.Password) {
+        return Err(OldPasswordIsInvalidError::from_str("Password is invalid").into());
+    }
+
+    conn.execute(&RecoveryCode::delete_builder()
+                     .filter("PersonID = $1")
+                     .build(),
+                 &[&user.id])
+        .unwrap();
+
+    conn.execute(&TwoFactor::delete_builder()
+                     .filter("PersonID = $1")
+                     .build(),
+                 &[&user.id])
+        .unwrap();
+
+    Ok(Response::with(StatusCode::Ok))
+}
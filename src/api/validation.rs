@@ -0,0 +1,173 @@
+use postgres::error::{Error, SqlState};
+
+use ::proto::error::*;
+use ::proto::schema::SignupData;
+
+const MIN_LOGIN_LEN: usize = 3;
+const MAX_LOGIN_LEN: usize = 64;
+// Matches varchar(255) columns of Person
+const MAX_FIELD_LEN: usize = 255;
+const MIN_PASSWORD_LEN: usize = 8;
+// Hashing is deliberately slow, arbitrarily long input would make it slower still
+const MAX_PASSWORD_LEN: usize = 1024;
+
+// Names of unique constraints Postgres generates for Person columns
+const LOGIN_CONSTRAINT: &'static str = "person_login_key";
+const EMAIL_CONSTRAINT: &'static str = "person_email_key";
+
+pub fn login(login: &str) -> Option<ErrorDetail> {
+    let len = login.chars().count();
+
+    if len < MIN_LOGIN_LEN || len > MAX_LOGIN_LEN {
+        return Some(ErrorDetail::new("Login",
+                                     &format!("Must be {} to {} characters long",
+                                              MIN_LOGIN_LEN,
+                                              MAX_LOGIN_LEN)));
+    }
+
+    let allowed = |c: char| match c {
+        'a'...'z' | 'A'...'Z' | '0'...'9' | '_' | '-' | '.' => true,
+        _ => false,
+    };
+
+    if !login.chars().all(allowed) {
+        return Some(ErrorDetail::new("Login",
+                                     "May contain only latin letters, digits, '_', '-' and '.'"));
+    }
+
+    None
+}
+
+pub fn name(name: &str) -> Option<ErrorDetail> {
+    if name.trim().is_empty() {
+        return Some(ErrorDetail::new("Name", "Must not be empty"));
+    }
+
+    if name.chars().count() > MAX_FIELD_LEN {
+        return Some(ErrorDetail::new("Name",
+                                     &format!("Must be at most {} characters long",
+                                              MAX_FIELD_LEN)));
+    }
+
+    None
+}
+
+pub fn password(field: &str, password: &str) -> Option<ErrorDetail> {
+    let len = password.chars().count();
+
+    if len < MIN_PASSWORD_LEN || len > MAX_PASSWORD_LEN {
+        return Some(ErrorDetail::new(field,
+                                     &format!("Must be {} to {} characters long",
+                                              MIN_PASSWORD_LEN,
+                                              MAX_PASSWORD_LEN)));
+    }
+
+    None
+}
+
+// Deliberately loose, actual ownership is proven by email verification
+pub fn email(field: &str, email: &str) -> Option<ErrorDetail> {
+    if email.chars().count() > MAX_FIELD_LEN {
+        return Some(ErrorDetail::new(field,
+                                     &format!("Must be at most {} characters long",
+                                              MAX_FIELD_LEN)));
+    }
+
+    let mut parts = email.splitn(2, '@');
+    let local = parts.next().unwrap_or("");
+    let domain = parts.next().unwrap_or("");
+
+    let well_formed = !local.is_empty() && !domain.contains('@') &&
+                      domain.split('.').count() > 1 &&
+                      domain.split('.').all(|label| !label.is_empty()) &&
+                      !email.chars().any(char::is_whitespace);
+
+    if !well_formed {
+        return Some(ErrorDetail::new(field, "Must be a valid email address"));
+    }
+
+    None
+}
+
+// Reports every invalid field at once rather than the first one
+pub fn fields(checks: Vec<Option<ErrorDetail>>) -> ApiResult<()> {
+    let details = checks.into_iter()
+        .filter_map(|detail| detail)
+        .collect::<Vec<ErrorDetail>>();
+
+    if details.is_empty() {
+        Ok(())
+    } else {
+        Err(box InvalidFieldsError::with_details("Some fields are invalid", details))
+    }
+}
+
+pub fn signup(data: &SignupData) -> ApiResult<()> {
+    fields(vec![login(&data.Login),
+                name(&data.Name),
+                email("Email", &data.Email),
+                password("Password", &data.Password)])
+}
+
+// Turns failed insert or update of Person into an error the client can act on.
+// Anything but a known unique violation is not the client's fault
+pub fn person_error(err: Error) -> Box<ApiError> {
+    if let Error::Db(ref db_err) = err {
+        if db_err.code == SqlState::UniqueViolation {
+            let taken = |field: &str| vec![ErrorDetail::new(field, "Already taken")];
+
+            match db_err.constraint.as_ref().map(String::as_str) {
+                Some(LOGIN_CONSTRAINT) => {
+                    return box LoginTakenError::with_details("Login is already taken",
+                                                             taken("Login"));
+                }
+                Some(EMAIL_CONSTRAINT) => {
+                    return box EmailTakenError::with_details("Email is already taken",
+                                                             taken("Email"));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    error!("person update failed: {:?}", err);
+    box DatabaseError::from_str("Database error, try again later")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+    use super::*;
+
+    fn long(len: usize) -> String {
+        iter::repeat('a').take(len).collect()
+    }
+
+    #[test]
+    fn logins() {
+        assert!(login("john_doe-1.0").is_none());
+        assert!(login("jo").is_some());
+        assert!(login(&long(MAX_LOGIN_LEN + 1)).is_some());
+        assert!(login("john doe").is_some());
+        assert!(login("джон").is_some());
+    }
+
+    #[test]
+    fn names() {
+        assert!(name("John Doe").is_none());
+        assert!(name("  ").is_some());
+        assert!(name(&long(MAX_FIELD_LEN + 1)).is_some());
+    }
+
+    #[test]
+    fn emails() {
+        assert!(email("Email", "john@example.com").is_none());
+        assert!(email("Email", "john.doe+hotels@mail.example.org").is_none());
+        assert!(email("Email", "john").is_some());
+        assert!(email("Email", "@example.com").is_some());
+        assert!(email("Email", "john@example").is_some());
+        assert!(email("Email", "john@example.").is_some());
+        assert!(email("Email", "john@@example.com").is_some());
+        assert!(email("Email", "john doe@example.com").is_some());
+    }
+}
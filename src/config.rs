@@ -0,0 +1,56 @@
+use std::env;
+use std::str::FromStr;
+use dotenv::dotenv;
+
+lazy_static! {
+    // Lifetime of a session token, in seconds
+    pub static ref TOKEN_TTL: i64 = var_or("TOKEN_TTL", 24 * 60 * 60);
+
+    // How long resolved roles are cached before hitting the database again, in seconds
+    pub static ref ROLES_CACHE_TTL: i64 = var_or("ROLES_CACHE_TTL", 60);
+
+    // PBKDF2 rounds for new password hashes, older hashes are upgraded on signin
+    pub static ref PASSWORD_HASH_ITERATIONS: u32 = var_or("PASSWORD_HASH_ITERATIONS", 100000);
+
+    // Lifetime of a password reset token, in seconds
+    pub static ref PASSWORD_RESET_TTL: i64 = var_or("PASSWORD_RESET_TTL", 60 * 60);
+
+    // Lifetime of an email verification token, in seconds
+    pub static ref EMAIL_VERIFICATION_TTL: i64 = var_or("EMAIL_VERIFICATION_TTL", 3 * 24 * 60 * 60);
+
+    // Failed signins allowed per login before it gets locked out
+    pub static ref SIGNIN_LOGIN_ATTEMPTS: i32 = var_or("SIGNIN_LOGIN_ATTEMPTS", 5);
+
+    // Failed signins allowed per client address, higher as addresses may be shared
+    pub static ref SIGNIN_ADDR_ATTEMPTS: i32 = var_or("SIGNIN_ADDR_ATTEMPTS", 20);
+
+    // First lockout length, doubled with every further failure, in seconds
+    pub static ref SIGNIN_LOCKOUT_BASE: i64 = var_or("SIGNIN_LOCKOUT_BASE", 30);
+
+    pub static ref SIGNIN_LOCKOUT_MAX: i64 = var_or("SIGNIN_LOCKOUT_MAX", 60 * 60);
+
+    // Failure counters older than this start from scratch, in seconds
+    pub static ref SIGNIN_FAILURE_WINDOW: i64 = var_or("SIGNIN_FAILURE_WINDOW", 24 * 60 * 60);
+
+    // Time to enter the second factor after password was accepted, in seconds
+    pub static ref TWO_FACTOR_PENDING_TTL: i64 = var_or("TWO_FACTOR_PENDING_TTL", 5 * 60);
+
+    // Issuer shown next to the account in authenticator apps
+    pub static ref TWO_FACTOR_ISSUER: String = var_or("TWO_FACTOR_ISSUER", "HotelManagementSystem".to_owned());
+
+    // Directory to drop outgoing mail into, stdout is used when unset
+    pub static ref MAIL_DIR: Option<String> = var("MAIL_DIR");
+
+    pub static ref MAIL_FROM: String = var_or("MAIL_FROM", "noreply@localhost".to_owned());
+}
+
+fn var(name: &str) -> Option<String> {
+    dotenv().ok();
+    env::var(name).ok()
+}
+
+fn var_or<T: FromStr>(name: &str, default: T) -> T {
+    var(name)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
@@ -1,7 +1,6 @@
 use super::QueryBuilder;
 use super::Substitute;
 use std::borrow::Cow;
-use std::char;
 
 const INSERT_DEFAULT_TEMPLATE: &'static str = "INSERT INTO $table ($columns) VALUES \
                                                ($placeholders);";
@@ -57,7 +56,7 @@ impl<'a> QueryBuilder<'a> for InsertQueryBuilder<'a> {
             for (i, column) in self.columns.into_iter().enumerate() {
                 columns.push_str(&column);
                 placeholders.push('$');
-                placeholders.push(char::from_digit((i + 1) as u32, 10).unwrap());
+                placeholders.push_str(&(i + 1).to_string());
                 if i < len - 1 {
                     columns.push(',');
                     placeholders.push(',');
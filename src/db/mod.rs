@@ -46,6 +46,23 @@ mod tests {
             .collect()
     }
 
+    #[test]
+    fn many_placeholders() {
+        let columns = (1..13).map(|i| format!("C{}", i)).collect::<Vec<String>>();
+
+        let insert = columns.iter()
+            .fold(InsertQueryBuilder::default().table("T"),
+                  |builder, column| builder.set(column.clone()))
+            .build();
+        assert!(insert.contains("$9,$10,$11,$12)"));
+
+        let update = columns.iter()
+            .fold(UpdateQueryBuilder::default().table("T"),
+                  |builder, column| builder.set(column.clone()))
+            .build();
+        assert!(update.contains("C12=$12"));
+    }
+
     #[test]
     fn auto_insert() {
         let conn = get_db_connection();
@@ -1,6 +1,6 @@
 use postgres::rows::Row;
 use postgres::types::ToSql;
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
 
 use super::*;
 
@@ -10,30 +10,19 @@ auto_queries!(
         pub Login: String,
         pub Name: String,
         pub Email: String,
-        pub PassHash: String
+        pub PassHash: String,
+        pub EmailVerified: bool
     }
 );
 
 auto_queries!(
-    pub struct Owner {
+    pub struct Admin {
         pub PersonID: i32
     }
 );
 
 auto_queries!(
-    pub struct Manager {
-        pub PersonID: i32
-    }
-);
-
-auto_queries!(
-    pub struct Receptionist {
-        pub PersonID: i32
-    }
-);
-
-auto_queries!(
-    pub struct Cleaner {
+    pub struct Owner {
         pub PersonID: i32
     }
 );
@@ -130,7 +119,8 @@ auto_queries!(
 auto_queries!(
     pub struct EmployedIn {
         pub PersonID: i32,
-        pub HotelID: i32
+        pub HotelID: i32,
+        pub Role: String
     }
 );
 
@@ -153,7 +143,15 @@ auto_queries!(
         pub ArrivalTime: NaiveDateTime,
         pub DepartureTime: NaiveDateTime,
         pub FullCost: i32,
-        pub Paid: bool
+        pub Paid: bool,
+        pub Cancelled: bool,
+        pub CancelledBy: Option<i32>,
+        pub CancelledAt: Option<NaiveDateTime>,
+        pub CancellationFee: i32,
+        pub Status: String,
+        pub CheckedInAt: Option<NaiveDateTime>,
+        pub CheckedOutAt: Option<NaiveDateTime>,
+        pub ReservationID: Option<i32>
     }
 );
 
@@ -170,4 +168,173 @@ auto_queries!(
         pub ToCleanID: i32,
         pub CleanerPersonID: i32
     }
-);
\ No newline at end of file
+);
+
+auto_queries!(
+    pub struct Session {
+        pub ID: i32,
+        pub Token: String,
+        pub PersonID: i32,
+        pub Created: NaiveDateTime,
+        pub LastSeen: NaiveDateTime,
+        pub Expires: NaiveDateTime,
+        pub UserAgent: Option<String>,
+        pub Pending2FA: bool
+    }
+);
+
+auto_queries!(
+    pub struct PasswordReset {
+        pub ID: i32,
+        pub Token: String,
+        pub PersonID: i32,
+        pub Created: NaiveDateTime,
+        pub Expires: NaiveDateTime,
+        pub Used: bool
+    }
+);
+
+auto_queries!(
+    pub struct EmailVerification {
+        pub ID: i32,
+        pub Token: String,
+        pub PersonID: i32,
+        pub Email: String,
+        pub Created: NaiveDateTime,
+        pub Expires: NaiveDateTime,
+        pub Used: bool
+    }
+);
+
+auto_queries!(
+    pub struct ApiKey {
+        pub ID: i32,
+        pub PersonID: i32,
+        pub Name: String,
+        pub KeyHash: String,
+        pub Scopes: String,
+        pub Created: NaiveDateTime,
+        pub LastUsed: Option<NaiveDateTime>,
+        pub Revoked: bool
+    }
+);
+
+auto_queries!(
+    pub struct ApiKeyHotel {
+        pub ApiKeyID: i32,
+        pub HotelID: i32
+    }
+);
+
+auto_queries!(
+    pub struct SigninFailure {
+        pub Key: String,
+        pub Failures: i32,
+        pub LastFailure: NaiveDateTime,
+        pub LockedUntil: Option<NaiveDateTime>
+    }
+);
+
+auto_queries!(
+    pub struct Lockout {
+        pub ID: i32,
+        pub PersonID: Option<i32>,
+        pub Login: String,
+        pub Address: String,
+        pub Failures: i32,
+        pub LockedAt: NaiveDateTime,
+        pub LockedUntil: NaiveDateTime
+    }
+);
+
+auto_queries!(
+    pub struct TwoFactor {
+        pub PersonID: i32,
+        pub Secret: String,
+        pub Enabled: bool,
+        pub Created: NaiveDateTime,
+        pub LastStep: Option<i64>
+    }
+);
+
+auto_queries!(
+    pub struct RecoveryCode {
+        pub ID: i32,
+        pub PersonID: i32,
+        pub CodeHash: String,
+        pub Used: bool
+    }
+);
+
+auto_queries!(
+    pub struct AuthEvent {
+        pub ID: i32,
+        pub PersonID: Option<i32>,
+        pub Login: Option<String>,
+        pub Kind: String,
+        pub Address: Option<String>,
+        pub UserAgent: Option<String>,
+        pub Created: NaiveDateTime
+    }
+);
+
+auto_queries!(
+    pub struct BookingChange {
+        pub ID: i32,
+        pub BookingID: i32,
+        pub ChangedBy: i32,
+        pub ChangedAt: NaiveDateTime,
+        pub OldRoomNumber: i32,
+        pub OldArrivalTime: NaiveDateTime,
+        pub OldDepartureTime: NaiveDateTime,
+        pub OldFullCost: i32,
+        pub NewRoomNumber: i32,
+        pub NewArrivalTime: NaiveDateTime,
+        pub NewDepartureTime: NaiveDateTime,
+        pub NewFullCost: i32,
+        pub PriceDifference: i32
+    }
+);
+
+auto_queries!(
+    pub struct BookingNight {
+        pub BookingID: i32,
+        pub Night: NaiveDate,
+        pub BaseRate: i32,
+        pub DiscountPercentage: i32,
+        pub Price: i32
+    }
+);
+
+auto_queries!(
+    pub struct LedgerEntry {
+        pub ID: i32,
+        pub BookingID: i32,
+        pub Kind: String,
+        pub Amount: i32,
+        pub Method: Option<String>,
+        pub Reference: Option<String>,
+        pub RefundOf: Option<i32>,
+        pub RecordedBy: Option<i32>,
+        pub Created: NaiveDateTime
+    }
+);
+
+auto_queries!(
+    pub struct Invoice {
+        pub ID: i32,
+        pub HotelID: i32,
+        pub Number: i32,
+        pub BookingID: i32,
+        pub IssuedAt: NaiveDateTime
+    }
+);
+
+auto_queries!(
+    pub struct Reservation {
+        pub ID: i32,
+        pub LeadPersonID: i32,
+        pub HotelID: i32,
+        pub CreatedAt: NaiveDateTime
+    }
+);
@@ -5,33 +5,35 @@ auto_struct_from_row!(
         pub ID: i32,
         pub Login: String,
         pub Name: String,
-        pub Email: String
+        pub Email: String,
+        pub EmailVerified: bool
     }
 );
 
 pub mod procedure {
-    use postgres::Connection;
+    use postgres::{Connection, GenericConnection};
+    use postgres::error::Error;
     use chrono::NaiveDateTime;
     use ::db::schema::*;
 
-    pub fn insert_booking(conn: &Connection,
+    pub fn insert_booking(conn: &GenericConnection,
                           ClientPersonID: i32,
                           HotelID: i32,
                           RoomNumber: i32,
                           BookingTime: NaiveDateTime,
                           ArrivalTime: NaiveDateTime,
-                          DepartureTime: NaiveDateTime)
-                          -> i32 {
-        conn.query("SELECT insert_booking_and_return_id($1, $2, $3, $4, $5, $6);",
+                          DepartureTime: NaiveDateTime,
+                          ReservationID: Option<i32>)
+                          -> Result<i32, Error> {
+        conn.query("SELECT insert_booking_and_return_id($1, $2, $3, $4, $5, $6, $7);",
                    &[&ClientPersonID,
                      &HotelID,
                      &RoomNumber,
                      &BookingTime,
                      &ArrivalTime,
-                     &DepartureTime])
-            .unwrap()
-            .get(0)
-            .get(0)
+                     &DepartureTime,
+                     &ReservationID])
+            .map(|rows| rows.get(0).get(0))
     }
 
     pub fn insert_hotel(conn: &Connection, hotel: Hotel) -> i32 {
@@ -0,0 +1,318 @@
+use std::fmt::Write;
+use chrono::NaiveDateTime;
+
+// Courier 10pt on A4 with 50pt margins
+const PAGE_WIDTH: i32 = 595;
+const PAGE_HEIGHT: i32 = 842;
+const MARGIN: i32 = 50;
+const LINE_HEIGHT: i32 = 12;
+const LINES_PER_PAGE: usize = 60;
+const TEXT_WIDTH: usize = 80;
+const AMOUNT_WIDTH: usize = 12;
+
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub description: String,
+    pub amount: i32,
+}
+
+// Refunds have negative amount
+#[derive(Debug, Clone)]
+pub struct Payment {
+    pub date: NaiveDateTime,
+    pub description: String,
+    pub amount: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub number: String,
+    pub issued_at: NaiveDateTime,
+    pub hotel_name: String,
+    pub client_name: String,
+    pub booking_id: i32,
+    pub room_number: i32,
+    pub arrival: NaiveDateTime,
+    pub departure: NaiveDateTime,
+    pub lines: Vec<Line>,
+    pub total: i32,
+    pub tax_percentage: i32,
+    pub tax: i32,
+    pub payments: Vec<Payment>,
+    pub balance: i32,
+}
+
+// Prices are quoted with tax, this is the part of them that is tax
+pub fn included_tax(total: i32, tax_percentage: i32) -> i32 {
+    if tax_percentage <= 0 {
+        return 0;
+    }
+
+    (total as i64 * tax_percentage as i64 / (100 + tax_percentage as i64)) as i32
+}
+
+impl Document {
+    pub fn html(&self) -> String {
+        let mut html = String::with_capacity(4096);
+
+        write!(html,
+               "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Invoice \
+                {0}</title>\n</head>\n<body>\n<h1>{1}</h1>\n<h2>Invoice {0}</h2>\n<p>Issued \
+                {2}<br>\nGuest: {3}<br>\nBooking {4}, room {5}, {6} to {7}</p>\n",
+               escape(&self.number),
+               escape(&self.hotel_name),
+               self.issued_at,
+               escape(&self.client_name),
+               self.booking_id,
+               self.room_number,
+               self.arrival,
+               self.departure)
+            .unwrap();
+
+        html.push_str("<table>\n<tr><th>Description</th><th>Amount</th></tr>\n");
+        for line in &self.lines {
+            write!(html,
+                   "<tr><td>{}</td><td>{}</td></tr>\n",
+                   escape(&line.description),
+                   line.amount)
+                .unwrap();
+        }
+        write!(html,
+               "<tr><th>Total</th><th>{}</th></tr>\n<tr><td>Included tax {}%</td><td>{}</td>\
+                </tr>\n</table>\n",
+               self.total,
+               self.tax_percentage,
+               self.tax)
+            .unwrap();
+
+        html.push_str("<h3>Payments</h3>\n<table>\n<tr><th>Date</th><th>Description</th>\
+                       <th>Amount</th></tr>\n");
+        for payment in &self.payments {
+            write!(html,
+                   "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                   payment.date,
+                   escape(&payment.description),
+                   payment.amount)
+                .unwrap();
+        }
+        write!(html,
+               "<tr><th colspan=\"2\">Balance due</th><th>{}</th></tr>\n</table>\n</body>\n\
+                </html>\n",
+               self.balance)
+            .unwrap();
+
+        html
+    }
+
+    pub fn pdf(&self) -> Vec<u8> {
+        render_pdf(&self.text_lines())
+    }
+
+    // Plain text layout, PDF has no layout engine of its own
+    fn text_lines(&self) -> Vec<String> {
+        let rule = (0..TEXT_WIDTH).map(|_| '-').collect::<String>();
+        let mut lines = vec![self.hotel_name.clone(),
+                             format!("Invoice {}", self.number),
+                             format!("Issued {}", self.issued_at),
+                             format!("Guest: {}", self.client_name),
+                             format!("Booking {}, room {}, {} to {}",
+                                     self.booking_id,
+                                     self.room_number,
+                                     self.arrival,
+                                     self.departure),
+                             String::new(),
+                             rule.clone()];
+
+        for line in &self.lines {
+            lines.push(columns(&line.description, line.amount));
+        }
+
+        lines.push(rule.clone());
+        lines.push(columns("Total", self.total));
+        lines.push(columns(&format!("Included tax {}%", self.tax_percentage), self.tax));
+        lines.push(String::new());
+        lines.push("Payments".to_owned());
+        lines.push(rule.clone());
+
+        for payment in &self.payments {
+            lines.push(columns(&format!("{} {}", payment.date.date(), payment.description),
+                               payment.amount));
+        }
+
+        lines.push(rule);
+        lines.push(columns("Balance due", self.balance));
+        lines
+    }
+}
+
+fn columns(description: &str, amount: i32) -> String {
+    let width = TEXT_WIDTH - AMOUNT_WIDTH;
+    let description = description.chars().take(width - 1).collect::<String>();
+    format!("{:<width$}{:>amount_width$}",
+            description,
+            amount,
+            width = width,
+            amount_width = AMOUNT_WIDTH)
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Standard fonts only cover ASCII reliably, anything else is replaced
+fn pdf_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '(' | ')' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            ' '...'~' => escaped.push(c),
+            _ => escaped.push('?'),
+        }
+    }
+    escaped
+}
+
+// Minimal PDF 1.4 of monospaced text pages, enough for printing
+fn render_pdf(lines: &[String]) -> Vec<u8> {
+    let mut pages = lines.chunks(LINES_PER_PAGE).collect::<Vec<&[String]>>();
+    if pages.is_empty() {
+        pages.push(&[]);
+    }
+
+    let kids = (0..pages.len())
+        .map(|i| format!("{} 0 R", 4 + 2 * i))
+        .collect::<Vec<String>>();
+
+    let mut objects = vec!["<< /Type /Catalog /Pages 2 0 R >>".to_owned(),
+                           format!("<< /Type /Pages /Kids [{}] /Count {} >>",
+                                   kids.join(" "),
+                                   pages.len()),
+                           "<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>".to_owned()];
+
+    for (i, page) in pages.iter().enumerate() {
+        let mut content = format!("BT\n/F1 10 Tf\n{} TL\n{} {} Td\n",
+                                  LINE_HEIGHT,
+                                  MARGIN,
+                                  PAGE_HEIGHT - MARGIN);
+        for line in page.iter() {
+            write!(content, "({}) Tj T*\n", pdf_text(line)).unwrap();
+        }
+        content.push_str("ET");
+
+        objects.push(format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources \
+                              << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                             PAGE_WIDTH,
+                             PAGE_HEIGHT,
+                             5 + 2 * i));
+        objects.push(format!("<< /Length {} >>\nstream\n{}\nendstream", content.len(), content));
+    }
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        write!(pdf, "{} 0 obj\n{}\nendobj\n", i + 1, object).unwrap();
+    }
+
+    let xref = pdf.len();
+    write!(pdf, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).unwrap();
+    for offset in offsets {
+        write!(pdf, "{:010} 00000 n \n", offset).unwrap();
+    }
+    write!(pdf,
+           "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+           objects.len() + 1,
+           xref)
+        .unwrap();
+
+    pdf.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use super::*;
+    use super::render_pdf;
+
+    fn document() -> Document {
+        let day = |day| NaiveDate::from_ymd(2030, 1, day).and_hms(12, 0, 0);
+
+        Document {
+            number: "1-000001".to_owned(),
+            issued_at: day(5),
+            hotel_name: "Grand <Hotel> & Spa".to_owned(),
+            client_name: "John (Jr.) Doe".to_owned(),
+            booking_id: 1,
+            room_number: 1,
+            arrival: day(1),
+            departure: day(3),
+            lines: vec![Line {
+                            description: "Night of 2030-01-01".to_owned(),
+                            amount: 110,
+                        },
+                        Line {
+                            description: "Night of 2030-01-02".to_owned(),
+                            amount: 110,
+                        }],
+            total: 220,
+            tax_percentage: 10,
+            tax: 20,
+            payments: vec![Payment {
+                               date: day(1),
+                               description: "Payment (Cash)".to_owned(),
+                               amount: 200,
+                           }],
+            balance: 20,
+        }
+    }
+
+    #[test]
+    fn taxes() {
+        assert_eq!(included_tax(220, 10), 20);
+        assert_eq!(included_tax(220, 0), 0);
+        assert_eq!(included_tax(0, 20), 0);
+    }
+
+    #[test]
+    fn html() {
+        let html = document().html();
+        assert!(html.contains("Grand &lt;Hotel&gt; &amp; Spa"));
+        assert!(html.contains("Night of 2030-01-02"));
+        assert!(html.contains("<th>20</th>"));
+    }
+
+    #[test]
+    fn pdf() {
+        let pdf = String::from_utf8(document().pdf()).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4\n"));
+        assert!(pdf.ends_with("%%EOF\n"));
+        assert!(pdf.contains("(John \\(Jr.\\) Doe) Tj"));
+
+        // Cross reference table must point exactly at the objects
+        let xref = pdf.rfind("startxref\n").unwrap();
+        let xref_offset = pdf[xref + 10..].lines().next().unwrap().parse::<usize>().unwrap();
+        assert!(pdf[xref_offset..].starts_with("xref\n"));
+        let first_object = pdf[xref_offset..].lines().nth(3).unwrap()[..10].parse::<usize>()
+            .unwrap();
+        assert!(pdf[first_object..].starts_with("1 0 obj\n"));
+
+        let lines = (0..130).map(|i| i.to_string()).collect::<Vec<String>>();
+        let pdf = String::from_utf8(render_pdf(&lines)).unwrap();
+        assert!(pdf.contains("/Count 3"));
+    }
+}
@@ -0,0 +1,77 @@
+use std::io;
+use std::io::Write;
+use std::fs::File;
+use std::path::PathBuf;
+use chrono;
+
+use ::config;
+
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+impl Mail {
+    fn render(&self) -> String {
+        format!("From: {}\r\nTo: {}\r\nSubject: {}\r\n\r\n{}\r\n",
+                *config::MAIL_FROM,
+                self.to,
+                self.subject,
+                self.body)
+    }
+}
+
+pub trait Mailer: Send + Sync {
+    fn send(&self, mail: &Mail) -> io::Result<()>;
+}
+
+// Prints mail to stdout, handy for local development
+pub struct StdoutMailer;
+
+impl Mailer for StdoutMailer {
+    fn send(&self, mail: &Mail) -> io::Result<()> {
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        handle.write_all(mail.render().as_bytes())?;
+        handle.flush()
+    }
+}
+
+// Writes every mail into its own .eml file in given directory
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        FileMailer { dir: dir.into() }
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, mail: &Mail) -> io::Result<()> {
+        let name = format!("{}-{}.eml",
+                           chrono::UTC::now().format("%Y%m%d%H%M%S%f"),
+                           mail.to.replace(|c: char| !c.is_alphanumeric(), "_"));
+
+        let mut file = File::create(self.dir.join(name))?;
+        file.write_all(mail.render().as_bytes())
+    }
+}
+
+lazy_static! {
+    static ref MAILER: Box<Mailer> = match *config::MAIL_DIR {
+        Some(ref dir) => box FileMailer::new(dir.clone()),
+        None => box StdoutMailer,
+    };
+}
+
+pub fn send(mail: Mail) {
+    info!("sending mail {:?} to {}", mail.subject, mail.to);
+
+    if let Err(err) = MAILER.send(&mail) {
+        error!("failed to send mail to {}: {}", mail.to, err);
+    }
+}
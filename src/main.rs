@@ -4,7 +4,6 @@
 #![feature(log_syntax)]
 #![allow(non_snake_case)]
 
-#[cfg(test)] extern crate rand;
 #[macro_use] extern crate lazy_static;
 #[macro_use] extern crate router;
 #[macro_use] extern crate log;
@@ -21,12 +20,18 @@ extern crate oven;
 extern crate rustc_serialize;
 extern crate dotenv;
 extern crate chrono;
+extern crate rand;
+extern crate crypto;
 
 use iron::prelude::*;
 use iron::AfterMiddleware;
 
 #[macro_use]
 mod proto;
+mod config;
+mod mailer;
+mod payment;
+mod invoice;
 mod db;
 mod api;
 
@@ -36,17 +41,51 @@ fn main() {
     let router = router! (
         signin:                   post   "/api/signin/"                  => api::authorization::signin,
         signup:                   post   "/api/signup/"                  => api::authorization::signup,
+        signin_2fa:               post   "/api/signin/2fa"               => api::authorization::signin_2fa,
+        signout:                  post   "/api/signout/"                 => api::authorization::signout,
         
         city_get_cities:          get    "/api/city/"                    => api::city::get_cities,
         city_put_city:            put    "/api/city/"                    => api::city::put_city,
+        city_update_city:         post   "/api/city/:id"                 => api::city::update_city,
         
         account_get_all_bookings: get    "/api/account/bookings/"        => api::account::get_bookings,
         account_get_n_bookings:   get    "/api/account/bookings/:cnt"    => api::account::get_bookings,
         account_get_info:         get    "/api/account/"                 => api::account::get_account_info,
         account_update_info:      post   "/api/account/"                 => api::account::update_account_info,
+        account_delete:           delete "/api/account/"                 => api::account::delete_account,
+        account_export:           get    "/api/account/export"           => api::account::export_account,
+        account_get_sessions:     get    "/api/account/sessions/"        => api::account::get_sessions,
+        account_revoke_session:   delete "/api/account/sessions/:id"     => api::account::revoke_session,
+        account_password_reset:   post   "/api/account/password-reset/"  => api::account::request_password_reset,
+        account_password_reset_confirm: post "/api/account/password-reset/confirm" => api::account::confirm_password_reset,
+        account_verify_email:     post   "/api/account/verify-email/"    => api::account::request_email_verification,
+        account_verify_email_confirm: post "/api/account/verify-email/confirm" => api::account::confirm_email_verification,
+        account_2fa_enroll:       post   "/api/account/2fa/"             => api::twofactor::enroll,
+        account_2fa_confirm:      post   "/api/account/2fa/confirm"      => api::twofactor::confirm,
+        account_2fa_disable:      post   "/api/account/2fa/disable"      => api::twofactor::disable,
+
+        apikey_put_key:           put    "/api/apikey/"                  => api::apikey::put_api_key,
+        apikey_get_keys:          get    "/api/apikeys/"                 => api::apikey::get_api_keys,
+        apikey_revoke_key:        delete "/api/apikey/:id"               => api::apikey::revoke_api_key,
+
+        owner_get_lockouts:       get    "/api/owner/lockouts/"          => api::lockout::get_lockouts,
         
+        booking_get_all_bookings: get    "/api/bookings/"                => api::booking::get_bookings,
+        booking_get_n_bookings:   get    "/api/bookings/:cnt"            => api::booking::get_bookings,
         booking_get_booking:      get    "/api/booking/:id"              => api::booking::get_booking_by_id,
         booking_put_booking:      put    "/api/booking/"                 => api::booking::put_booking,
+        booking_update_booking:   post   "/api/booking/:id"              => api::booking::update_booking,
+        booking_cancel_booking:   post   "/api/booking/:id/cancel"       => api::booking::cancel_booking,
+        booking_check_in:         post   "/api/booking/:id/checkin"      => api::booking::check_in,
+        booking_check_out:        post   "/api/booking/:id/checkout"     => api::booking::check_out,
+        booking_update_status:    post   "/api/booking/:id/status"       => api::booking::update_status,
+        booking_get_payments:     get    "/api/booking/:id/payments"     => api::payment::get_payments,
+        booking_record_payment:   post   "/api/booking/:id/payments"     => api::payment::record_payment,
+        booking_pay_online:       post   "/api/booking/:id/pay"          => api::payment::pay_online,
+        booking_get_invoice:      get    "/api/booking/:id/invoice"      => api::invoice::get_invoice,
+        
+        reservation_get:          get    "/api/reservation/:id"          => api::reservation::get_reservation,
+        reservation_put:          put    "/api/reservation/"             => api::reservation::put_reservation,
         
         hotel_get_all_hotels:     get    "/api/hotels/"                  => api::hotel::get_hotels,
         hotel_get_n_hotels:       get    "/api/hotels/:cnt"              => api::hotel::get_hotels,
@@ -62,6 +101,8 @@ fn main() {
         hotel_get_n_reviews:      get    "/api/hotel/:id/reviews/:cnt"   => api::hotel::get_reviews,
         hotel_get_all_employees:  get    "/api/hotel/:id/employees/"     => api::hotel::get_employees,
         hotel_get_n_employees:    get    "/api/hotel/:id/employees/:cnt" => api::hotel::get_employees,
+        hotel_put_employee:       put    "/api/hotel/:id/employee/"      => api::hotel::hire_employee,
+        hotel_update_employee:    post   "/api/hotel/:id/employee/:eid"  => api::hotel::update_employee,
         hotel_del_employee:       delete "/api/hotel/:id/employee/:eid"  => api::hotel::fire_employee,
 
         manager_get_all_rulesets: get    "/api/manager/rulesets/"        => api::manager::get_rulesets,
@@ -70,6 +111,15 @@ fn main() {
         manager_update_ruleset:   post   "/api/manager/ruleset/:id"      => api::manager::update_ruleset,
         manager_delete_ruleset:   delete "/api/manager/ruleset/:id"      => api::manager::delete_ruleset,
 
+        audit_get_all_auth_events: get   "/api/audit/auth/"              => api::audit::get_auth_events,
+        audit_get_n_auth_events:  get    "/api/audit/auth/:cnt"          => api::audit::get_auth_events,
+        admin_get_all_persons:    get    "/api/admin/persons/"           => api::admin::get_persons,
+        admin_get_n_persons:      get    "/api/admin/persons/:cnt"       => api::admin::get_persons,
+        admin_update_person:      post   "/api/admin/person/:id"         => api::admin::update_person,
+        admin_set_hotel_owner:    post   "/api/admin/hotel/:id/owner"    => api::admin::set_hotel_owner,
+        admin_get_default_ruleset: get   "/api/admin/ruleset/default"    => api::admin::get_default_ruleset,
+        admin_update_default_ruleset: post "/api/admin/ruleset/default"  => api::admin::update_default_ruleset,
+
         search:                   post   "/api/search/"                  => api::search::search
         
     );
@@ -113,7 +163,8 @@ impl AfterMiddleware for CorsMiddleware {
 		UniCase("Origin".to_owned()),
 		UniCase("Content-Type".to_owned()),
 		UniCase("Accept".to_owned()),
-		UniCase("token".to_owned())
+		UniCase("token".to_owned()),
+		UniCase("Authorization".to_owned())
 	]));
         Ok(res)
     }
@@ -0,0 +1,128 @@
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+#[derive(Debug, Clone)]
+pub struct Charge {
+    pub booking_id: i32,
+    pub amount: i32,
+    // Card details never reach us, only the token the client got from the gateway
+    pub card_token: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Receipt {
+    pub reference: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum PaymentError {
+    // Card was refused, retrying won't help
+    Declined(String),
+    // Gateway couldn't be reached, worth retrying later
+    Unavailable(String),
+}
+
+impl fmt::Display for PaymentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PaymentError::Declined(ref reason) => write!(f, "Payment declined: {}", reason),
+            PaymentError::Unavailable(ref reason) => {
+                write!(f, "Payment gateway unavailable: {}", reason)
+            }
+        }
+    }
+}
+
+pub trait PaymentGateway: Send + Sync {
+    fn charge(&self, charge: &Charge) -> Result<Receipt, PaymentError>;
+    fn refund(&self, reference: &str, amount: i32) -> Result<Receipt, PaymentError>;
+}
+
+const FAKE_DECLINED_TOKEN: &'static str = "tok_declined";
+const FAKE_UNAVAILABLE_TOKEN: &'static str = "tok_unavailable";
+const FAKE_CHARGE_PREFIX: &'static str = "fake-charge-";
+
+static FAKE_SEQUENCE: AtomicUsize = ATOMIC_USIZE_INIT;
+
+// Approves every card but two magic tokens, so tests can exercise every outcome
+pub struct FakeGateway;
+
+impl FakeGateway {
+    fn next_reference(prefix: &str) -> String {
+        format!("{}{}", prefix, FAKE_SEQUENCE.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+}
+
+impl PaymentGateway for FakeGateway {
+    fn charge(&self, charge: &Charge) -> Result<Receipt, PaymentError> {
+        match charge.card_token.as_str() {
+            FAKE_DECLINED_TOKEN => Err(PaymentError::Declined("Insufficient funds".to_owned())),
+            FAKE_UNAVAILABLE_TOKEN => Err(PaymentError::Unavailable("Timed out".to_owned())),
+            _ if charge.amount <= 0 => {
+                Err(PaymentError::Declined("Amount must be positive".to_owned()))
+            }
+            _ => Ok(Receipt { reference: FakeGateway::next_reference(FAKE_CHARGE_PREFIX) }),
+        }
+    }
+
+    fn refund(&self, reference: &str, amount: i32) -> Result<Receipt, PaymentError> {
+        if !reference.starts_with(FAKE_CHARGE_PREFIX) {
+            return Err(PaymentError::Declined(format!("Unknown charge {}", reference)));
+        }
+
+        if amount <= 0 {
+            return Err(PaymentError::Declined("Amount must be positive".to_owned()));
+        }
+
+        Ok(Receipt { reference: FakeGateway::next_reference("fake-refund-") })
+    }
+}
+
+lazy_static! {
+    // Real gateway plugs in here, nothing but the fake one exists yet
+    static ref GATEWAY: Box<PaymentGateway> = box FakeGateway;
+}
+
+pub fn gateway() -> &'static PaymentGateway {
+    &**GATEWAY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn charge(card_token: &str, amount: i32) -> Result<Receipt, PaymentError> {
+        FakeGateway.charge(&Charge {
+            booking_id: 1,
+            amount: amount,
+            card_token: card_token.to_owned(),
+        })
+    }
+
+    #[test]
+    fn fake_charges() {
+        let first = charge("tok_visa", 100).unwrap();
+        let second = charge("tok_visa", 100).unwrap();
+        assert!(first.reference != second.reference);
+
+        match charge(FAKE_DECLINED_TOKEN, 100) {
+            Err(PaymentError::Declined(_)) => (),
+            other => panic!("{:?}", other),
+        }
+
+        match charge(FAKE_UNAVAILABLE_TOKEN, 100) {
+            Err(PaymentError::Unavailable(_)) => (),
+            other => panic!("{:?}", other),
+        }
+
+        assert!(charge("tok_visa", 0).is_err());
+    }
+
+    #[test]
+    fn fake_refunds() {
+        let receipt = charge("tok_visa", 100).unwrap();
+        assert!(FakeGateway.refund(&receipt.reference, 50).is_ok());
+        assert!(FakeGateway.refund("cash-1", 50).is_err());
+        assert!(FakeGateway.refund(&receipt.reference, 0).is_err());
+    }
+}
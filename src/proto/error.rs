@@ -13,6 +13,16 @@ pub enum ErrorCode {
     NotAuthorizedError,
     OldPasswordIsInvalidError,
     NotFoundError,
+    InvalidTokenError,
+    EmailNotVerifiedError,
+    AccountLockedError,
+    InvalidFieldsError,
+    LoginTakenError,
+    EmailTakenError,
+    DatabaseError,
+    ConflictError,
+    PaymentDeclinedError,
+    PaymentGatewayError,
 }
 
 new_api_error!(InvalidSchemaError);
@@ -22,6 +32,16 @@ new_api_error!(SignupError);
 new_api_error!(NotAuthorizedError);
 new_api_error!(OldPasswordIsInvalidError);
 new_api_error!(NotFoundError);
+new_api_error!(InvalidTokenError);
+new_api_error!(EmailNotVerifiedError);
+new_api_error!(AccountLockedError);
+new_api_error!(InvalidFieldsError);
+new_api_error!(LoginTakenError);
+new_api_error!(EmailTakenError);
+new_api_error!(DatabaseError);
+new_api_error!(ConflictError);
+new_api_error!(PaymentDeclinedError);
+new_api_error!(PaymentGatewayError);
 
 api_error_gen_from_error!(json::DecoderError, InvalidSchemaError);
 
@@ -31,12 +51,41 @@ impl Into<i32> for ErrorCode {
     }
 }
 
+// Points at the request field that caused an error, so clients can show it next to the input
+#[derive(Debug, Clone, RustcEncodable)]
+pub struct ErrorDetail {
+    pub field: String,
+    pub reason: String,
+}
+
+impl ErrorDetail {
+    pub fn new(field: &str, reason: &str) -> Self {
+        ErrorDetail {
+            field: field.to_owned(),
+            reason: reason.to_owned(),
+        }
+    }
+}
+
+#[derive(RustcEncodable)]
+struct ErrorBody<'a> {
+    err_code: String,
+    description: &'a str,
+    details: &'a [ErrorDetail],
+}
+
 pub trait ApiError: Error + Send {
     fn code(&self) -> i32;
+    fn details(&self) -> &[ErrorDetail] {
+        &[]
+    }
     fn json(&self) -> String {
-        format!("{{\"err_code\":\"{}\", \"description\":\"{}\"}}",
-                self.code(),
-                self.description())
+        json::encode(&ErrorBody {
+                err_code: format!("{}", self.code()),
+                description: self.description(),
+                details: self.details(),
+            })
+            .unwrap()
     }
 }
 
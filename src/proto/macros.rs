@@ -3,6 +3,7 @@ macro_rules! new_api_error {
         #[derive(Debug, Clone)]
         pub struct $ident {
             description: String,
+            details: Vec<ErrorDetail>,
         }
 
         impl ::std::fmt::Display for $ident {
@@ -15,6 +16,10 @@ macro_rules! new_api_error {
             fn code(&self) -> i32 {
                 ErrorCode::$ident.into()
             }
+
+            fn details(&self) -> &[ErrorDetail] {
+                &self.details
+            }
         }
 
         impl ::std::error::Error for $ident {
@@ -41,7 +46,17 @@ macro_rules! new_api_error {
                 where U: Into<::std::borrow::Cow<'static, str>> + Display
             {
                 Self {
-                    description: format!("{}", &desc)
+                    description: format!("{}", &desc),
+                    details: Vec::new(),
+                }
+            }
+
+            pub fn with_details<U>(desc: U, details: Vec<ErrorDetail>) -> Self
+                where U: Into<::std::borrow::Cow<'static, str>> + Display
+            {
+                Self {
+                    description: format!("{}", &desc),
+                    details: details,
                 }
             }
         }
@@ -1,40 +1,187 @@
+use std::fmt;
+use std::str::FromStr;
+use std::collections::BTreeMap;
 use chrono::NaiveDateTime;
 use postgres::Connection;
-use ::db::schema::Person;
-use ::db::schema::Room;
+use ::db::schema::{Room, Booking, BookingNight, LedgerEntry, Reservation, Review, MaintainedBy};
+use ::db::schemaext::AccountInfo;
 use ::db::*;
 
-#[derive(Debug, RustcDecodable)]
+// Stands in for credentials in Debug output, so they never reach the logs
+const MASKED: &'static str = "***";
+
+#[derive(RustcDecodable)]
 pub struct SigninData {
     pub Login: String,
-    pub PassHash: String,
+    pub Password: String,
+}
+
+impl fmt::Debug for SigninData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SigninData")
+            .field("Login", &self.Login)
+            .field("Password", &MASKED)
+            .finish()
+    }
 }
 
-#[derive(Debug, RustcDecodable)]
+#[derive(RustcEncodable, RustcDecodable)]
 pub struct SignupData {
     pub Login: String,
     pub Name: String,
     pub Email: String,
-    pub PassHash: String,
+    pub Password: String,
 }
 
+impl fmt::Debug for SignupData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SignupData")
+            .field("Login", &self.Login)
+            .field("Name", &self.Name)
+            .field("Email", &self.Email)
+            .field("Password", &MASKED)
+            .finish()
+    }
+}
+
+// Manager, Cleaner and Receptionist tell if person has the role in any hotel,
+// HotelRoles maps each hotel person is employed in to the role held there
 #[derive(Debug, Clone, Eq, PartialEq, RustcEncodable, RustcDecodable)]
 pub struct Roles {
     pub ID: i32,
+    // Platform administrator, acts as owner of every hotel
+    pub Admin: bool,
     pub Owner: bool,
     pub Owns: Option<Vec<i32>>,
     pub Manager: bool,
     pub Cleaner: bool,
     pub Receptionist: bool,
     pub EmployedIn: Option<Vec<i32>>,
+    pub HotelRoles: BTreeMap<i32, StaffRole>,
+}
+
+// Token is also set as a cookie, body copy is for non-browser clients.
+// With Pending2FA set the token is only good for completing the signin
+#[derive(Debug, Clone, RustcEncodable, RustcDecodable)]
+pub struct SigninResponse {
+    pub Token: String,
+    pub Roles: Option<Roles>,
+    pub Pending2FA: bool,
+}
+
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct TwoFactorSignin {
+    pub Token: String,
+    pub Code: String,
+}
+
+impl fmt::Debug for TwoFactorSignin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TwoFactorSignin")
+            .field("Token", &MASKED)
+            .field("Code", &MASKED)
+            .finish()
+    }
+}
+
+// Secret is shown only during enrollment, Uri carries it for QR codes
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct TwoFactorEnrollment {
+    pub Secret: String,
+    pub Uri: String,
+}
+
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct TwoFactorCode {
+    pub Code: String,
+}
+
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct RecoveryCodes {
+    pub RecoveryCodes: Vec<String>,
+}
+
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct TwoFactorDisable {
+    pub Password: String,
 }
 
+// Everything stored about a person, MaintainedBookings is their receptionist history
 #[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct AccountExport {
+    pub Profile: AccountInfo,
+    pub Bookings: Vec<Booking>,
+    pub Reviews: Vec<Review>,
+    pub MaintainedBookings: Vec<MaintainedBy>,
+    pub ExportedAt: NaiveDateTime,
+}
+
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct AccountDeletion {
+    pub Password: String,
+}
+
+impl fmt::Debug for AccountDeletion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AccountDeletion")
+            .field("Password", &MASKED)
+            .finish()
+    }
+}
+
+#[derive(RustcEncodable, RustcDecodable)]
 pub struct UpdateAccountInfoData {
     pub NewName: Option<String>,
     pub NewEmail: Option<String>,
-    pub OldPassHash: Option<String>,
-    pub NewPassHash: Option<String>,
+    pub OldPassword: Option<String>,
+    pub NewPassword: Option<String>,
+}
+
+impl fmt::Debug for UpdateAccountInfoData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UpdateAccountInfoData")
+            .field("NewName", &self.NewName)
+            .field("NewEmail", &self.NewEmail)
+            .field("OldPassword", &self.OldPassword.as_ref().map(|_| MASKED))
+            .field("NewPassword", &self.NewPassword.as_ref().map(|_| MASKED))
+            .finish()
+    }
+}
+
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct PasswordResetRequest {
+    pub Login: Option<String>,
+    pub Email: Option<String>,
+}
+
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct PasswordResetConfirm {
+    pub Token: String,
+    pub NewPassword: String,
+}
+
+impl fmt::Debug for PasswordResetConfirm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PasswordResetConfirm")
+            .field("Token", &MASKED)
+            .field("NewPassword", &MASKED)
+            .finish()
+    }
+}
+
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct EmailVerificationConfirm {
+    pub Token: String,
+}
+
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct SessionInfo {
+    pub ID: i32,
+    pub Created: NaiveDateTime,
+    pub LastSeen: NaiveDateTime,
+    pub Expires: NaiveDateTime,
+    pub UserAgent: Option<String>,
+    pub Current: bool,
 }
 
 #[derive(Debug, RustcEncodable, RustcDecodable)]
@@ -42,6 +189,21 @@ pub struct NewCity {
     pub Name: String,
 }
 
+// Person edits available to administrators, role flags grant or take away the role
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct AdminUpdatePerson {
+    pub Name: Option<String>,
+    pub Email: Option<String>,
+    pub EmailVerified: Option<bool>,
+    pub Owner: Option<bool>,
+    pub Admin: Option<bool>,
+}
+
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct HotelOwner {
+    pub OwnerPersonID: i32,
+}
+
 #[derive(Debug, RustcEncodable, RustcDecodable)]
 pub struct NewBooking {
     pub ClientPersonID: Option<i32>,
@@ -51,6 +213,80 @@ pub struct NewBooking {
     pub DepartureTime: NaiveDateTime,
 }
 
+// Every room is booked or none is, ClientPersonID is the lead guest
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct NewReservation {
+    pub ClientPersonID: Option<i32>,
+    pub HotelID: i32,
+    pub Rooms: Vec<ReservationRoom>,
+}
+
+#[derive(Debug, Clone, RustcEncodable, RustcDecodable)]
+pub struct ReservationRoom {
+    pub RoomNumber: i32,
+    pub ArrivalTime: NaiveDateTime,
+    pub DepartureTime: NaiveDateTime,
+}
+
+// Rooms are cancelled one by one, FullCost counts only the fee for those
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct ReservationDetails {
+    pub Reservation: Reservation,
+    pub Bookings: Vec<Booking>,
+    pub FullCost: i32,
+}
+
+// Check-in and check-out have endpoints of their own, cancellation goes through /cancel
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct NewBookingStatus {
+    pub Status: BookingStatus,
+}
+
+// Nights add up to booking's FullCost
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct BookingDetails {
+    pub Booking: Booking,
+    pub Nights: Vec<BookingNight>,
+}
+
+// Fields left out keep their current values
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct UpdateBooking {
+    pub RoomNumber: Option<i32>,
+    pub ArrivalTime: Option<NaiveDateTime>,
+    pub DepartureTime: Option<NaiveDateTime>,
+}
+
+// Positive Balance is what guest still owes
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct BookingLedger {
+    pub Entries: Vec<LedgerEntry>,
+    pub Balance: i32,
+}
+
+// Money taken or returned at the desk, Reference is the terminal's receipt number
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct NewPayment {
+    pub Kind: LedgerKind,
+    pub Method: PaymentMethod,
+    pub Amount: i32,
+    pub Reference: Option<String>,
+}
+
+// Pays the whole balance, CardToken comes from the gateway's client side
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct OnlinePayment {
+    pub CardToken: String,
+}
+
+impl fmt::Debug for OnlinePayment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OnlinePayment")
+            .field("CardToken", &MASKED)
+            .finish()
+    }
+}
+
 #[derive(Debug, RustcEncodable, RustcDecodable)]
 pub struct NewHotel {
     pub CityID: i32,
@@ -81,10 +317,221 @@ pub struct UpdateRoom {
     pub PhotoSetID: Option<i32>
 }
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq, RustcEncodable, RustcDecodable)]
+pub enum StaffRole {
+    Manager,
+    Receptionist,
+    Cleaner,
+}
+
+impl StaffRole {
+    pub fn all() -> [StaffRole; 3] {
+        [StaffRole::Manager, StaffRole::Receptionist, StaffRole::Cleaner]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            StaffRole::Manager => "Manager",
+            StaffRole::Receptionist => "Receptionist",
+            StaffRole::Cleaner => "Cleaner",
+        }
+    }
+}
+
+impl FromStr for StaffRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Manager" => Ok(StaffRole::Manager),
+            "Receptionist" => Ok(StaffRole::Receptionist),
+            "Cleaner" => Ok(StaffRole::Cleaner),
+            _ => Err(format!("Unknown role {:?}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, RustcEncodable, RustcDecodable)]
+pub enum BookingStatus {
+    Reserved,
+    Confirmed,
+    CheckedIn,
+    CheckedOut,
+    NoShow,
+    Cancelled,
+}
+
+impl BookingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            BookingStatus::Reserved => "Reserved",
+            BookingStatus::Confirmed => "Confirmed",
+            BookingStatus::CheckedIn => "CheckedIn",
+            BookingStatus::CheckedOut => "CheckedOut",
+            BookingStatus::NoShow => "NoShow",
+            BookingStatus::Cancelled => "Cancelled",
+        }
+    }
+
+    // Nothing happens to a booking after it's over
+    pub fn is_final(&self) -> bool {
+        match *self {
+            BookingStatus::CheckedOut | BookingStatus::NoShow | BookingStatus::Cancelled => true,
+            _ => false,
+        }
+    }
+
+    pub fn can_become(&self, next: BookingStatus) -> bool {
+        match (*self, next) {
+            (BookingStatus::Reserved, BookingStatus::Confirmed) => true,
+            (BookingStatus::Reserved, BookingStatus::CheckedIn) |
+            (BookingStatus::Confirmed, BookingStatus::CheckedIn) => true,
+            (BookingStatus::Reserved, BookingStatus::NoShow) |
+            (BookingStatus::Confirmed, BookingStatus::NoShow) => true,
+            (BookingStatus::Reserved, BookingStatus::Cancelled) |
+            (BookingStatus::Confirmed, BookingStatus::Cancelled) => true,
+            (BookingStatus::CheckedIn, BookingStatus::CheckedOut) => true,
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for BookingStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Reserved" => Ok(BookingStatus::Reserved),
+            "Confirmed" => Ok(BookingStatus::Confirmed),
+            "CheckedIn" => Ok(BookingStatus::CheckedIn),
+            "CheckedOut" => Ok(BookingStatus::CheckedOut),
+            "NoShow" => Ok(BookingStatus::NoShow),
+            "Cancelled" => Ok(BookingStatus::Cancelled),
+            _ => Err(format!("Unknown booking status {:?}", s)),
+        }
+    }
+}
+
+// Charges are recorded by the database as the booking's cost changes
+#[derive(Debug, Clone, Copy, Eq, PartialEq, RustcEncodable, RustcDecodable)]
+pub enum LedgerKind {
+    Charge,
+    Payment,
+    Deposit,
+    Refund,
+}
+
+impl LedgerKind {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            LedgerKind::Charge => "Charge",
+            LedgerKind::Payment => "Payment",
+            LedgerKind::Deposit => "Deposit",
+            LedgerKind::Refund => "Refund",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, RustcEncodable, RustcDecodable)]
+pub enum PaymentMethod {
+    Cash,
+    Card,
+    Online,
+}
+
+impl PaymentMethod {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            PaymentMethod::Cash => "Cash",
+            PaymentMethod::Card => "Card",
+            PaymentMethod::Online => "Online",
+        }
+    }
+}
+
 #[derive(Debug, RustcEncodable, RustcDecodable)]
 pub struct Employee {
-    pub Person: Person,
-    pub Roles: Roles,
+    pub Person: AccountInfo,
+    pub Role: StaffRole,
+}
+
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct NewEmployee {
+    pub PersonID: i32,
+    pub Role: StaffRole,
+}
+
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct UpdateEmployee {
+    pub Role: StaffRole,
+}
+
+// Permissions an API key can be granted, sessions implicitly have all of them
+#[derive(Debug, Clone, Copy, Eq, PartialEq, RustcEncodable, RustcDecodable)]
+pub enum Scope {
+    ReadBookings,
+    WriteBookings,
+    ReadHotels,
+    WriteHotels,
+    WriteRates,
+    ReadStaff,
+    WriteStaff,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Scope::ReadBookings => "ReadBookings",
+            Scope::WriteBookings => "WriteBookings",
+            Scope::ReadHotels => "ReadHotels",
+            Scope::WriteHotels => "WriteHotels",
+            Scope::WriteRates => "WriteRates",
+            Scope::ReadStaff => "ReadStaff",
+            Scope::WriteStaff => "WriteStaff",
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ReadBookings" => Ok(Scope::ReadBookings),
+            "WriteBookings" => Ok(Scope::WriteBookings),
+            "ReadHotels" => Ok(Scope::ReadHotels),
+            "WriteHotels" => Ok(Scope::WriteHotels),
+            "WriteRates" => Ok(Scope::WriteRates),
+            "ReadStaff" => Ok(Scope::ReadStaff),
+            "WriteStaff" => Ok(Scope::WriteStaff),
+            _ => Err(format!("Unknown scope {:?}", s)),
+        }
+    }
+}
+
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct NewApiKey {
+    pub Name: String,
+    pub HotelIDs: Vec<i32>,
+    pub Scopes: Vec<Scope>,
+}
+
+// Key itself is shown only once, on creation
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct CreatedApiKey {
+    pub ID: i32,
+    pub Key: String,
+}
+
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct ApiKeyInfo {
+    pub ID: i32,
+    pub Name: String,
+    pub HotelIDs: Vec<i32>,
+    pub Scopes: Vec<Scope>,
+    pub Created: NaiveDateTime,
+    pub LastUsed: Option<NaiveDateTime>,
+    pub Revoked: bool,
 }
 
 #[derive(Debug, RustcEncodable, RustcDecodable)]
@@ -140,3 +587,27 @@ impl PricedRoom {
 
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::BookingStatus;
+    use super::BookingStatus::*;
+
+    #[test]
+    fn booking_transitions() {
+        assert!(Reserved.can_become(Confirmed));
+        assert!(Reserved.can_become(CheckedIn));
+        assert!(Confirmed.can_become(NoShow));
+        assert!(Confirmed.can_become(Cancelled));
+        assert!(CheckedIn.can_become(CheckedOut));
+
+        assert!(!Reserved.can_become(CheckedOut));
+        assert!(!Confirmed.can_become(Reserved));
+        assert!(!CheckedIn.can_become(Cancelled));
+
+        let all = [Reserved, Confirmed, CheckedIn, CheckedOut, NoShow, Cancelled];
+        for status in all.iter().filter(|status| status.is_final()) {
+            assert!(all.iter().all(|next: &BookingStatus| !status.can_become(*next)));
+        }
+    }
+}